@@ -0,0 +1,31 @@
+//! Cost of filtering records by message, run with `cargo bench`
+//!
+//! Compares a plain substring, which is searched for without the regex
+//! engine, with an equivalent regex.
+
+extern crate slog;
+extern crate slog_envlogger;
+
+use std::time::Instant;
+
+use slog_envlogger::LogBuilder;
+
+const RECORDS: u32 = 1_000_000;
+
+fn bench(name: &str, spec: &str) {
+    let logger = LogBuilder::new(slog::Discard).parse(spec).build();
+    let log = slog::Logger::root(logger, slog::o!());
+
+    let start = Instant::now();
+    for i in 0..RECORDS {
+        slog::info!(log, "request {} finished after a timeout of {} ms", i, i % 100);
+    }
+    let elapsed = start.elapsed();
+    println!("{:<10} {:>8.1} ns/record", name, elapsed.as_nanos() as f64 / f64::from(RECORDS));
+}
+
+fn main() {
+    bench("literal", "info/timeout");
+    bench("regex", "info/time[o]ut");
+    bench("no filter", "info");
+}
@@ -0,0 +1,64 @@
+//! Cost of matching many glob directives, run with
+//! `cargo bench --features globset`
+//!
+//! Compares 30 `GlobMatcher` directives, which the `globset` feature
+//! matches all at once, with the same globs matched one by one.
+
+extern crate slog;
+extern crate slog_envlogger;
+
+use std::time::Instant;
+
+use slog::{FilterLevel, Level};
+use slog_envlogger::{GlobMatcher, LogBuilder, ModuleMatcher};
+
+const LOOKUPS: u32 = 1_000_000;
+
+const MODULES: &[&str] = &[
+    "app::server::http::conn",
+    "app::db::pool",
+    "hyper::proto::h1::io",
+    "tokio::runtime::task",
+    "app::jobs::queue::worker7",
+];
+
+/// A glob matched one by one, as it doesn't tell its pattern
+#[derive(Debug)]
+struct Unindexed(GlobMatcher);
+
+impl ModuleMatcher for Unindexed {
+    fn matches(&self, module: &str) -> bool {
+        self.0.matches(module)
+    }
+}
+
+fn globs() -> Vec<GlobMatcher> {
+    (0..28).map(|i| GlobMatcher(format!("*::subsystem{}::*", i))).chain(vec![
+        GlobMatcher("*::db::*".to_string()),
+        GlobMatcher("hyper::*::h?::*".to_string()),
+    ]).collect()
+}
+
+fn bench<M: ModuleMatcher + 'static>(name: &str, matchers: Vec<M>) {
+    let logger = matchers.into_iter()
+        .fold(LogBuilder::new(slog::Discard).parse("info"), |builder, matcher| {
+            builder.filter_matcher(matcher, FilterLevel::Debug)
+        })
+        .build();
+
+    let start = Instant::now();
+    let mut enabled = 0;
+    for i in 0..LOOKUPS {
+        if logger.would_log(Level::Debug, MODULES[i as usize % MODULES.len()]) {
+            enabled += 1;
+        }
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(enabled, LOOKUPS / MODULES.len() as u32 * 2);
+    println!("{:<10} {:>8.1} ns/lookup", name, elapsed.as_nanos() as f64 / f64::from(LOOKUPS));
+}
+
+fn main() {
+    bench("globset", globs());
+    bench("one by one", globs().into_iter().map(Unindexed).collect());
+}
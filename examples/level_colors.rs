@@ -0,0 +1,22 @@
+extern crate slog;
+extern crate slog_stdlog;
+extern crate slog_envlogger;
+
+#[macro_use]
+extern crate log;
+
+use slog::Level;
+
+fn main() {
+    // Run with eg. `RUST_LOG="debug;color=always"`
+    let _guard = slog_envlogger::init_with_level_colors(vec![
+        (Level::Error, 9),
+        (Level::Warning, 11),
+        (Level::Debug, 8),
+    ]).unwrap();
+
+    error!("error");
+    warn!("warning");
+    info!("info");
+    debug!("debug");
+}
@@ -0,0 +1,134 @@
+//! Replacing the directives of a logger over a Unix socket, with the
+//! `control-socket` feature
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use slog::{Drain, FilterLevel};
+use tokio::sync::watch;
+
+use crate::parse::{parse_spec, ParsedSpec, SpecOptions};
+use crate::{Directives, EnvLogger, MessageBuf};
+
+/// Longest spec read from a connection
+const MAX_SPEC_LEN: u64 = 64 * 1024;
+
+/// How long a client may take to send its spec
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Make `logger` take its directives from specs sent to the socket at `path`
+///
+/// The logger is left as is if the socket can't be set up.
+pub fn attach<T: Drain, B: MessageBuf>(logger: &mut EnvLogger<T, B>, path: &Path) -> io::Result<()> {
+    let (sender, receiver) = watch::channel(Directives::new(logger.directives.clone()));
+    let listener = spawn(path, sender)?;
+    logger.watch = Some(AssertUnwindSafe(receiver));
+    logger.control = Some(listener);
+    Ok(())
+}
+
+/// Stops the thread listening on the socket, and removes it, when dropped
+/// with the logger
+pub(crate) struct Listener {
+    path: PathBuf,
+    closed: Arc<AtomicBool>,
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Release);
+        // The thread only notices once it accepts a connection
+        let _ = UnixStream::connect(&self.path);
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn spawn(path: &Path, sender: watch::Sender<Directives>) -> io::Result<Listener> {
+    // A socket left by a previous run would make binding fail
+    if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let closed = Arc::new(AtomicBool::new(false));
+    let thread_closed = closed.clone();
+    let spawned = thread::Builder::new()
+        .name("envlogger-control".to_string())
+        .spawn(move || serve(listener, sender, &thread_closed));
+    if let Err(e) = spawned {
+        let _ = fs::remove_file(path);
+        return Err(e);
+    }
+    Ok(Listener { path: path.to_path_buf(), closed })
+}
+
+fn serve(listener: UnixListener, sender: watch::Sender<Directives>, closed: &AtomicBool) {
+    for stream in listener.incoming() {
+        // The logger was dropped
+        if closed.load(Ordering::Acquire) || sender.is_closed() {
+            break;
+        }
+        if let Ok(stream) = stream {
+            // The client went away, there's no one to tell
+            let _ = handle(stream, &sender);
+        }
+    }
+}
+
+/// Apply the spec sent on the first line of `stream`, replying `ok` or the
+/// problems found
+fn handle(mut stream: UnixStream, sender: &watch::Sender<Directives>) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    // Not read to the end, so clients needn't shut down their side to get
+    // the reply
+    let mut spec = String::new();
+    if let Err(e) = BufReader::new((&stream).take(MAX_SPEC_LEN)).read_line(&mut spec) {
+        return writeln!(stream, "error: {}", e);
+    }
+
+    let parsed = parse_spec(spec.trim());
+    let unapplied = unapplied_parts(&parsed);
+    if !parsed.errors.is_empty() || !unapplied.is_empty() {
+        // Half applied specs are hard to reason about, apply all or nothing
+        for e in &parsed.errors {
+            writeln!(stream, "error: {}", e)?;
+        }
+        for part in unapplied {
+            writeln!(stream, "error: {} can't be changed through the socket", part)?;
+        }
+        return Ok(());
+    }
+    let directives = parsed.resolved_directives(FilterLevel::Error);
+    if directives.is_empty() {
+        return writeln!(stream, "error: no directives in '{}'", spec.trim());
+    }
+    if sender.send(Directives::new(directives)).is_err() {
+        return writeln!(stream, "error: the logger was dropped");
+    }
+    writeln!(stream, "ok")
+}
+
+/// Parts of `parsed` other than module directives, which only apply when
+/// building a logger
+fn unapplied_parts(parsed: &ParsedSpec) -> Vec<&'static str> {
+    let mut parts = Vec::new();
+    if !parsed.kv_directives.is_empty() {
+        parts.push("key-value directives");
+    }
+    if parsed.filter.is_some() || parsed.filter_bound.is_some() || !parsed.level_filters.is_empty() ||
+        parsed.len_filter.is_some() || parsed.key_filter.is_some() || parsed.kv_threshold.is_some() ||
+        parsed.errors_only {
+        parts.push("filters");
+    }
+    if parsed.options != SpecOptions::default() {
+        parts.push("options");
+    }
+    parts
+}
@@ -0,0 +1,81 @@
+//! Matching all glob directives of a logger at once, with the `globset`
+//! feature
+
+extern crate globset;
+
+use self::globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::parse::LogDirective;
+
+/// Fewest glob directives worth a `GlobSet`, below that matching them one by
+/// one is about as fast
+const MIN_GLOBS: usize = 4;
+
+/// The glob directives of a logger, compiled into a single `GlobSet`
+pub struct GlobIndex {
+    set: GlobSet,
+    // Position of the directive of each glob in the set, ascending
+    directives: Vec<usize>,
+}
+
+impl GlobIndex {
+    /// Index the glob directives among `directives`, if there are enough
+    pub fn new(directives: &[LogDirective]) -> Option<GlobIndex> {
+        let mut builder = GlobSetBuilder::new();
+        let mut indexed = Vec::new();
+        for (i, directive) in directives.iter().enumerate() {
+            let pattern = match directive.matcher.as_ref().and_then(|matcher| matcher.glob()) {
+                Some(pattern) if is_plain(pattern) => pattern,
+                _ => continue,
+            };
+            // Globs `GlobSet` rejects, like `a**b`, are matched one by one
+            let glob = GlobBuilder::new(pattern)
+                .literal_separator(false)
+                .backslash_escape(false)
+                .build();
+            if let Ok(glob) = glob {
+                builder.add(glob);
+                indexed.push(i);
+            }
+        }
+        if indexed.len() < MIN_GLOBS {
+            return None;
+        }
+        builder.build().ok().map(|set| GlobIndex { set, directives: indexed })
+    }
+
+    /// The indexed directives matching `module`
+    pub fn matches(&self, module: &str) -> GlobMatches<'_> {
+        GlobMatches {
+            index: self,
+            matched: self.set.matches(module),
+        }
+    }
+}
+
+/// Result of `GlobIndex::matches`
+pub struct GlobMatches<'a> {
+    index: &'a GlobIndex,
+    // Positions of the matching globs in the set, ascending
+    matched: Vec<usize>,
+}
+
+impl<'a> GlobMatches<'a> {
+    /// Whether the directive at `i` is indexed, so `contains` decides whether
+    /// it matches
+    pub fn covers(&self, i: usize) -> bool {
+        self.index.directives.binary_search(&i).is_ok()
+    }
+
+    /// Whether the directive at `i` matches the module
+    pub fn contains(&self, i: usize) -> bool {
+        let directives = &self.index.directives;
+        self.matched.binary_search_by_key(&i, |&glob| directives[glob]).is_ok()
+    }
+}
+
+/// Whether `pattern` only has the wildcards of `GlobMatcher`, so `GlobSet`
+/// matches it the same way
+fn is_plain(pattern: &str) -> bool {
+    pattern.is_ascii() && !pattern.contains(['[', ']', '{', '}', '\\'])
+}
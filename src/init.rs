@@ -2,9 +2,13 @@ extern crate log;
 extern crate slog_scope;
 extern crate slog_stdlog;
 extern crate slog_term;
+#[cfg(feature = "async")]
+extern crate slog_async;
 
-use crate::new;
+use crate::{parse_spec, Color, Env, EnvLogger};
 use slog::*;
+use std::fmt;
+use std::io::{self, IsTerminal};
 use std::sync;
 
 /// Use a default `EnvLogger` as global logging drain
@@ -17,13 +21,253 @@ use std::sync;
 /// anything that `slog` has to offer, so I highly encourage to use `new()`
 /// instead and explicitly configure your loggers.
 pub fn init() -> std::result::Result<slog_scope::GlobalLoggerGuard, log::SetLoggerError> {
-    let drain =
-        slog_term::CompactFormat::new(slog_term::TermDecorator::new().stderr().build()).build();
-    let drain = new(drain);
-    let drain = sync::Mutex::new(drain.fuse());
+    init_from_env(Env::default())
+}
+
+/// Like `init()`, but reading the spec from `var` instead of `RUST_LOG`
+///
+/// No other variable is read for the configuration, not even `RUST_LOG_MIN`
+/// or `RUST_LOG_DEBUG`, see `Env::only`. (The terminal output may still look
+/// at `TERM` to find out about colors.)
+pub fn init_from(var: &str) -> std::result::Result<slog_scope::GlobalLoggerGuard, log::SetLoggerError> {
+    init_from_env(Env::only(var))
+}
+
+/// Same as `init()`, named after `env_logger::try_init`
+pub fn try_init() -> std::result::Result<slog_scope::GlobalLoggerGuard, log::SetLoggerError> {
+    init()
+}
+
+/// Like `init()`, but reading the spec from `env`
+///
+/// Eg. `init_from_env(Env::default().default_filter_or("info"))` logs at
+/// info level if `RUST_LOG` is unset.
+pub fn init_from_env<E: Into<Env>>(env: E)
+    -> std::result::Result<slog_scope::GlobalLoggerGuard, log::SetLoggerError> {
+    let env = env.into();
+    let spec = env.get_filter().unwrap_or_default();
+    let logger = root_logger(term_drain(&spec), env);
+
+    let guard = slog_scope::set_global_logger(logger);
+    slog_stdlog::init()?;
+
+    Ok(guard)
+}
+
+/// Like `init()`, but logging asynchronously through `slog-async`
+///
+/// Records are formatted and written by a separate thread, instead of being
+/// serialized through a `Mutex`. The `EnvLogger` wraps the async drain, so
+/// records are filtered before being sent to that thread, and suppressed
+/// ones cost no more than with `init()`.
+#[cfg(feature = "async")]
+pub fn init_async() -> std::result::Result<slog_scope::GlobalLoggerGuard, log::SetLoggerError> {
+    let env = Env::default();
+    let spec = env.get_filter().unwrap_or_default();
+    let drain = slog_async::Async::default(term_drain(&spec).fuse());
+    let (drain, warnings) = EnvLogger::from_env_collecting(drain, env);
+    let logger = Logger::root(drain.fuse(), o!());
+    log_warnings(&logger, &warnings);
 
-    let guard = slog_scope::set_global_logger(Logger::root(drain.fuse(), o!()).into_erased());
+    let guard = slog_scope::set_global_logger(logger);
     slog_stdlog::init()?;
 
     Ok(guard)
 }
+
+/// Like `init()`, but with custom colors for the level labels
+///
+/// `colors` maps levels to ANSI color numbers, `0` to `7` for black, red,
+/// green, yellow, blue, magenta, cyan and white, `8` to `15` for their
+/// bright variants. Levels without a color keep the `slog_term` default.
+/// Colors are only used if the color mode of the spec allows them, see
+/// the `color` option.
+///
+/// ```no_run
+/// # extern crate slog;
+/// # extern crate slog_envlogger;
+/// use slog::Level;
+///
+/// # fn main() {
+/// let _guard = slog_envlogger::init_with_level_colors(vec![
+///     (Level::Error, 9),
+///     (Level::Warning, 11),
+/// ]).unwrap();
+/// # }
+/// ```
+pub fn init_with_level_colors<I: IntoIterator<Item = (Level, u8)>>(colors: I)
+    -> std::result::Result<slog_scope::GlobalLoggerGuard, log::SetLoggerError> {
+    let env = Env::default();
+    let spec = env.get_filter().unwrap_or_default();
+    let decorator = LevelColors::new(term_decorator(&spec), colors, use_color(&spec));
+    let logger = root_logger(slog_term::CompactFormat::new(decorator).build(), env);
+
+    let guard = slog_scope::set_global_logger(logger);
+    slog_stdlog::init()?;
+
+    Ok(guard)
+}
+
+/// Root logger filtering `drain` with the spec from `env`
+///
+/// The logger is built first, and problems with the spec are then logged
+/// through it as warnings, so they end up formatted in the same stream as
+/// everything else rather than printed to stdout. Like any other record,
+/// they are subject to the spec.
+pub(crate) fn root_logger<D>(drain: D, env: Env) -> Logger
+where D: Drain<Ok = ()> + Send + 'static, D::Err: fmt::Debug {
+    let (drain, warnings) = EnvLogger::from_env_collecting(drain, env);
+    let logger = Logger::root(sync::Mutex::new(drain.fuse()).fuse(), o!());
+    log_warnings(&logger, &warnings);
+    logger
+}
+
+fn log_warnings(logger: &Logger, warnings: &sync::Mutex<Vec<String>>) {
+    let mut warnings = warnings.lock().unwrap_or_else(|e| e.into_inner());
+    for warning in warnings.drain(..) {
+        warn!(logger, "{}", warning);
+    }
+}
+
+/// Terminal drain, in the color mode of `spec`
+fn term_drain(spec: &str) -> slog_term::CompactFormat<slog_term::TermDecorator> {
+    slog_term::CompactFormat::new(term_decorator(spec)).build()
+}
+
+fn term_decorator(spec: &str) -> slog_term::TermDecorator {
+    let decorator = slog_term::TermDecorator::new().stderr();
+    let decorator = match parse_spec(spec).options.color {
+        Some(Color::Always) => decorator.force_color(),
+        Some(Color::Never) => decorator.force_plain(),
+        Some(Color::Auto) | None => decorator,
+    };
+    decorator.build()
+}
+
+/// Whether the color mode of `spec` enables colors on stderr
+fn use_color(spec: &str) -> bool {
+    match parse_spec(spec).options.color {
+        Some(Color::Always) => true,
+        Some(Color::Never) => false,
+        Some(Color::Auto) | None => io::stderr().is_terminal(),
+    }
+}
+
+/// Decorator coloring level labels, on top of another decorator
+pub(crate) struct LevelColors<D> {
+    inner: D,
+    // Indexed by `Level::as_usize`
+    colors: [Option<u8>; 7],
+}
+
+impl<D: slog_term::Decorator> LevelColors<D> {
+    pub(crate) fn new<I: IntoIterator<Item = (Level, u8)>>(inner: D, colors: I, use_color: bool) -> Self {
+        let mut by_level = [None; 7];
+        if use_color {
+            for (level, color) in colors {
+                by_level[level.as_usize()] = Some(color);
+            }
+        }
+        LevelColors { inner, colors: by_level }
+    }
+}
+
+impl<D: slog_term::Decorator> slog_term::Decorator for LevelColors<D> {
+    fn with_record<F>(&self, record: &Record, logger_values: &OwnedKVList, f: F) -> io::Result<()>
+    where F: FnOnce(&mut dyn slog_term::RecordDecorator) -> io::Result<()> {
+        let color = self.colors[record.level().as_usize()];
+        self.inner.with_record(record, logger_values, |inner| {
+            f(&mut LevelColorsRecord { inner, color, colored: false })
+        })
+    }
+}
+
+/// Record decorator of `LevelColors`, delegating everything but the level
+struct LevelColorsRecord<'a> {
+    inner: &'a mut dyn slog_term::RecordDecorator,
+    color: Option<u8>,
+    // Whether our own color is active, as the inner decorator won't reset it
+    colored: bool,
+}
+
+impl<'a> LevelColorsRecord<'a> {
+    fn end_color(&mut self) -> io::Result<()> {
+        if self.colored {
+            self.colored = false;
+            self.inner.write_all(b"\x1b[0m")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> io::Write for LevelColorsRecord<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<'a> slog_term::RecordDecorator for LevelColorsRecord<'a> {
+    fn reset(&mut self) -> io::Result<()> {
+        self.end_color()?;
+        self.inner.reset()
+    }
+
+    fn start_whitespace(&mut self) -> io::Result<()> {
+        self.end_color()?;
+        self.inner.start_whitespace()
+    }
+
+    fn start_msg(&mut self) -> io::Result<()> {
+        self.end_color()?;
+        self.inner.start_msg()
+    }
+
+    fn start_timestamp(&mut self) -> io::Result<()> {
+        self.end_color()?;
+        self.inner.start_timestamp()
+    }
+
+    fn start_level(&mut self) -> io::Result<()> {
+        self.end_color()?;
+        match self.color {
+            Some(color) => {
+                self.inner.reset()?;
+                // Basic colors are 30-37, bright ones 90-97
+                let code = if color < 8 { 30 + color } else { 90 + color % 8 };
+                write!(self.inner, "\x1b[{}m", code)?;
+                self.colored = true;
+                Ok(())
+            },
+            None => self.inner.start_level(),
+        }
+    }
+
+    fn start_comma(&mut self) -> io::Result<()> {
+        self.end_color()?;
+        self.inner.start_comma()
+    }
+
+    fn start_key(&mut self) -> io::Result<()> {
+        self.end_color()?;
+        self.inner.start_key()
+    }
+
+    fn start_value(&mut self) -> io::Result<()> {
+        self.end_color()?;
+        self.inner.start_value()
+    }
+
+    fn start_location(&mut self) -> io::Result<()> {
+        self.end_color()?;
+        self.inner.start_location()
+    }
+
+    fn start_separator(&mut self) -> io::Result<()> {
+        self.end_color()?;
+        self.inner.start_separator()
+    }
+}
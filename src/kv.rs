@@ -0,0 +1,137 @@
+//! Looking up values in a record's key-value pairs, and adding our own
+
+use std::fmt;
+
+use slog::{Key, OwnedKVList, Record, Serializer, KV};
+
+/// Serializer capturing the formatted value of the first occurrence of a key
+struct FindValue<'a> {
+    key: &'a str,
+    value: Option<String>,
+}
+
+impl<'a> Serializer for FindValue<'a> {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        if self.value.is_none() && key == self.key {
+            self.value = Some(format!("{}", val));
+        }
+        Ok(())
+    }
+}
+
+/// Serializer stopping at the first occurrence of a key, without formatting
+/// any value
+struct HasKey<'a> {
+    key: &'a str,
+    found: bool,
+}
+
+impl<'a> Serializer for HasKey<'a> {
+    fn emit_arguments(&mut self, key: Key, _: &fmt::Arguments) -> slog::Result {
+        if key == self.key {
+            self.found = true;
+            // Stop serializing, the rest doesn't matter
+            return Err(slog::Error::Other);
+        }
+        Ok(())
+    }
+}
+
+/// Serializer stopping at the first error value
+struct HasError {
+    found: bool,
+}
+
+impl Serializer for HasError {
+    fn emit_arguments(&mut self, _: Key, _: &fmt::Arguments) -> slog::Result {
+        Ok(())
+    }
+
+    fn emit_error(&mut self, _: Key, _: &(dyn std::error::Error + 'static)) -> slog::Result {
+        self.found = true;
+        Err(slog::Error::Other)
+    }
+}
+
+/// Serializer capturing all key-values, formatted
+struct Collect {
+    pairs: Vec<(String, String)>,
+}
+
+impl Serializer for Collect {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.pairs.push((key.to_string(), format!("{}", val)));
+        Ok(())
+    }
+}
+
+/// All formatted key-values, the record's own first, then the ones of the
+/// logger it was logged through
+pub fn collect(record: &Record, values: &OwnedKVList) -> Vec<(String, String)> {
+    let mut ser = Collect { pairs: Vec::new() };
+    let _ = record.kv().serialize(record, &mut ser);
+    let _ = values.serialize(record, &mut ser);
+    ser.pairs
+}
+
+/// Whether the record, or the logger it was logged through, carries `key`
+pub fn has_key(record: &Record, values: &OwnedKVList, key: &str) -> bool {
+    let mut ser = HasKey { key, found: false };
+    let _ = record.kv().serialize(record, &mut ser);
+    if !ser.found {
+        let _ = values.serialize(record, &mut ser);
+    }
+    ser.found
+}
+
+/// Whether the record, or the logger it was logged through, carries an
+/// error, eg. from `"err" => #e`
+pub fn has_error(record: &Record, values: &OwnedKVList) -> bool {
+    let mut ser = HasError { found: false };
+    let _ = record.kv().serialize(record, &mut ser);
+    if !ser.found {
+        let _ = values.serialize(record, &mut ser);
+    }
+    ser.found
+}
+
+/// Key-values added to records passed on, see `LogBuilder::backoff_dedup`
+/// and `LogBuilder::annotate_match`
+#[derive(Default)]
+pub struct Annotations {
+    /// Occurrences skipped by the backoff, `0` for no key-value
+    pub skipped: u64,
+    /// Rendered directive that enabled the record
+    pub matched_directive: Option<String>,
+}
+
+impl Annotations {
+    pub fn is_empty(&self) -> bool {
+        self.skipped == 0 && self.matched_directive.is_none()
+    }
+}
+
+impl KV for Annotations {
+    fn serialize(&self, _: &Record, serializer: &mut dyn Serializer) -> slog::Result {
+        if self.skipped > 0 {
+            serializer.emit_u64("skipped", self.skipped)?;
+        }
+        if let Some(ref directive) = self.matched_directive {
+            serializer.emit_str("matched_directive", directive)?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the formatted value of `key`
+///
+/// The record's own key-values are searched first, then the ones of the
+/// logger it was logged through.
+pub fn find_value(record: &Record, values: &OwnedKVList, key: &str) -> Option<String> {
+    let mut ser = FindValue { key, value: None };
+    let _ = record.kv().serialize(record, &mut ser);
+    if ser.value.is_none() {
+        let _ = values.serialize(record, &mut ser);
+    }
+    ser.value
+}
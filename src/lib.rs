@@ -30,13 +30,31 @@
 //! Furthermore, this path is a prefix-search, so all modules nested in the
 //! specified module will also have logging enabled.
 //!
+//! The search is textual: `app` also matches a crate named `apple`, and
+//! `myorg_` matches all crates of a workspace named `myorg_foo`, `myorg_bar`
+//! and so on. With `LogBuilder::boundary_aware`, a name only matches whole
+//! `::` separated segments, except for names ending with `_`, which are
+//! still taken as crate name prefixes. A leading `::`, as in
+//! `::mycrate::net=debug`, anchors a single directive the same way:
+//! it matches `mycrate::net` and its submodules but not `mycrate::network`.
+//!
 //! The actual `log_level` is optional to specify. If omitted, all logging will
 //! be enabled. If specified, it must be one of the strings `debug`, `error`,
-//! `info`, `warn`, or `trace`.
+//! `info`, `warn`, or `trace`, or any spelling `parse_filter_level` accepts,
+//! like `deb` or `4`.
 //!
 //! As the log level for a module is optional, the module to enable logging for
 //! is also optional. If only a `log_level` is provided, then the global log
-//! level for all modules is set to this value.
+//! level for all modules is set to this value. The same can be written
+//! explicitly with `*` as the module, eg. `*=info`.
+//!
+//! A value of just `off`, `0` or `none` (in any case) disables all logging.
+//! A floor that no directive can disable is set with the `RUST_LOG_MIN`
+//! variable, eg. `RUST_LOG_MIN=error` logs errors even with `RUST_LOG=off`.
+//!
+//! Setting `RUST_LOG_DEBUG=1` prints the effective configuration to stderr
+//! when a logger is created from the environment, which helps finding out
+//! why a module isn't logging.
 //!
 //! Some examples of valid values of `RUST_LOG` are:
 //!
@@ -45,6 +63,21 @@
 //! * `hello=debug` turns on debug logging for 'hello'
 //! * `hello,std::option` turns on hello, and std's option logging
 //! * `error,hello=warn` turn on global error logging and also warn for hello
+//! * `off,hello=debug` turns off all logging except debug logging for hello,
+//!   as module directives win over the global one wherever it appears
+//!
+//! A directive can also be written level first, as `log_level:path`, with
+//! the level abbreviated as far as it stays unambiguous, eg. `d:hello,w:net`
+//! is the same as `hello=debug,net=warn`.
+//!
+//! A directive starting with `@` matches records by a key-value instead of
+//! by module, in the form `@key=value=log_level`. The key is looked up in
+//! the record's key-values and the ones of the logger it was logged through,
+//! and a matching key-value directive takes precedence over module
+//! directives:
+//!
+//! * `off,@build=8jdkj2df=debug` turns on debug logging only for records
+//!   from a logger created with `o!("build" => "8jdkj2df")`
 //!
 //! ## Filtering results
 //!
@@ -65,23 +98,213 @@
 //! * `error,hello=warn/[0-9] scopes` turn on global error logging and also
 //!   warn for hello. In both cases the log message must include a single digit
 //!   number followed by 'scopes'.
+//! * `info//api/v1` turns on info logging where the log message includes
+//!   '/api/v1'. Only the first `/` separates the filter, so it can contain
+//!   more of them.
+//!
+//! The filter can be restricted to some levels with a `@` suffix, one of
+//! `@>=level`, `@>level`, `@<=level` or `@<level`. Records outside of the
+//! bound are logged regardless of the filter:
+//!
+//! * `debug/foo@<info` turns on debug logging, where debug messages have to
+//!   include 'foo', while info, warning and error messages are always logged.
+//!
+//! A level can also be given relative to the global one, as `+N` for N
+//! levels more verbose, or `-N` for N levels less verbose:
+//!
+//! * `info,noisy=-1,important=+2` turns on global info logging, warn for
+//!   'noisy' and trace for 'important'
+//! * `info,*=+1` turns on global debug logging
+//!
+//! Instead of a maximum level, a directive can select an exact set of levels,
+//! separated by `|`:
+//!
+//! * `hello=warn|error` turns on warning and error, but not critical logging
+//!   for the 'hello' module
+//!
+//! A directive prefixed with `!` takes priority over all others matching the
+//! same module, even longer ones. Each additional `!` raises the priority
+//! further:
+//!
+//! * `hello::db=trace,!hello=warn` logs warnings for all of `hello`,
+//!   including `hello::db`
+//!
+//! Programs can register named specs with `LogBuilder::profile`, which a
+//! `@profile:name` directive stands for, eg. `@profile:debugging,hyper=warn`.
+//!
+//! The byte length of the message can be filtered with a `@len` suffix,
+//! followed by one of `<`, `<=`, `>`, `>=` or `==` and a number:
+//!
+//! * `info/@len>1000` only logs messages longer than 1000 bytes
+//!
+//! A `@has:` suffix only logs records carrying a key, whatever its value,
+//! which is cheaper than matching the message:
+//!
+//! * `info/@has:trace_id` only logs records with a `trace_id` key, either
+//!   their own or of the logger they're logged through
+//!
+//! A key followed by a comparison compares the value of that key, which has
+//! to be a number, to a threshold:
+//!
+//! * `info/@duration_ms>500` only logs records with a `duration_ms` above 500
+//!
+//! Likewise, a `@errors` suffix only logs records carrying an error value,
+//! eg. `"err" => #e`:
+//!
+//! * `warn/@errors` only logs warnings and above that come with an error
+//!
+//! ## Options
+//!
+//! Global options can follow the directives and filter, each introduced by
+//! a `;`:
+//!
+//! * `color=auto|always|never` sets the color mode of the `init()` output
+//! * `sample=N` only logs every N-th record that passes filtering
+//! * `filter-disable=true` ignores the filter
+//!
+//! For example `info,hello=debug/foo;color=never;sample=10`.
+//!
+//! Likewise, a `;level/filter` replaces the filter for records of exactly
+//! that level, and `;level/` removes it for them. For example
+//! `debug/db;warn/;error/` only logs debug and info messages containing
+//! "db", but warnings and errors regardless of their message.
+//!
+//! ## Testing directives
+//!
+//! `LogBuilder::from_directives` builds a logger discarding all records,
+//! whose `would_log` tells if a configuration lets a record through:
+//!
+//! ```
+//! extern crate slog;
+//! extern crate slog_envlogger;
+//!
+//! use slog::{FilterLevel, Level};
+//! use slog_envlogger::{parse_spec, LogBuilder, LogDirective};
+//!
+//! # fn main() {
+//! let mut dirs = parse_spec("info,hyper=warn").directives;
+//! dirs.push(LogDirective::new(Some("myapp::db"), FilterLevel::Trace));
+//! let logger = LogBuilder::from_directives(dirs).build();
+//! assert!(logger.would_log(Level::Info, "myapp"));
+//! assert!(!logger.would_log(Level::Info, "hyper::client"));
+//! assert!(logger.would_log(Level::Trace, "myapp::db"));
+//! # }
+//! ```
+//!
+//! ## Regex engines
+//!
+//! Filters are regular expressions, matched with the `regex` crate (the
+//! default `regex` feature). For smaller binaries the `regex-lite` feature
+//! can be enabled instead, using the `regex-lite` crate. It has the same
+//! syntax, minus Unicode classes like `\p{Greek}` and case-insensitive
+//! Unicode matching, and is slower on big inputs. Like `regex`, it doesn't
+//! support look-around or backreferences. With neither feature, the filter is
+//! matched as a plain substring.
+//!
+//! A filter starting with `lit:` is always matched as a plain substring, for
+//! text with characters that are special in regexes, eg. `info/lit:a.b` only
+//! logs messages including `a.b`.
+//!
+//! ## Many glob directives
+//!
+//! Directives with a `GlobMatcher` are matched one by one. With dozens of
+//! them, the `globset` feature speeds this up, by compiling them into a
+//! single `globset::GlobSet` that's queried once per record. Directives
+//! naming modules are matched by prefix either way.
+//!
+//! ## Live control
+//!
+//! On Unix, the `control-socket` feature adds `LogBuilder::control_socket`,
+//! which listens on a socket for specs replacing the module directives of
+//! the running logger, eg.
+//! `echo 'info,app::db=trace' | socat - UNIX-CONNECT:/run/app/log.sock`.
+//!
+//! ## `no_std`
+//!
+//! Without the default `std` feature only the spec parser (`parse_spec`) is
+//! available. It needs just `alloc`, so directive strings obtained elsewhere
+//! can be parsed and applied to custom filtering.
 
 #![doc(html_logo_url = "http://www.rust-lang.org/logos/rust-logo-128x128-blk-v2.png",
        html_favicon_url = "http://www.rust-lang.org/favicon.ico",
        html_root_url = "http://doc.rust-lang.org/env_logger/")]
 #![cfg_attr(test, deny(warnings))]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
 extern crate slog;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "toml")]
+extern crate toml;
 
+#[cfg(feature = "std")]
 use std::{env, result};
-use std::cell::RefCell;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+#[cfg(feature = "std")]
+use std::fmt::Write;
+#[cfg(feature = "std")]
+use std::panic::{RefUnwindSafe, UnwindSafe};
+#[cfg(feature = "std")]
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::mem;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+#[cfg(feature = "std")]
+use std::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+#[cfg(feature = "std")]
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "std")]
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "std")]
+use std::sync::atomic::AtomicBool;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+#[cfg(feature = "std")]
 use slog::*;
+#[cfg(feature = "tokio")]
+use std::panic::AssertUnwindSafe;
+#[cfg(feature = "tokio")]
+use tokio::sync::watch;
+#[cfg(all(unix, feature = "control-socket"))]
+use std::path::{Path, PathBuf};
+
+mod parse;
+
+#[cfg(feature = "std")]
+mod kv;
+
+#[cfg(feature = "globset")]
+mod globs;
+
+#[cfg(all(unix, feature = "control-socket"))]
+mod control;
 
-#[cfg(feature = "regex")]
+pub use parse::{directives_to_spec, parse_filter_level, parse_spec, strip_comments, Color, Comparison, ExactMatcher, FilterBound,
+                FilterError, GlobMatcher, KeyPresenceFilter, KvDirective, KvThresholdFilter, LenFilter, LevelSet, LogDirective, ModuleMatcher, ParseError,
+                ParseReport, ParsedSpec, RelativeDirective, SpecOptions, SuffixMatcher};
+
+#[cfg(all(feature = "std", feature = "regex"))]
 #[path = "regex.rs"]
 mod filter;
 
-#[cfg(not(feature = "regex"))]
+#[cfg(all(feature = "std", feature = "regex-lite", not(feature = "regex")))]
+#[path = "regex_lite.rs"]
+mod filter;
+
+#[cfg(all(feature = "std", not(feature = "regex"), not(feature = "regex-lite")))]
 #[path = "string.rs"]
 mod filter;
 
@@ -90,401 +313,5440 @@ mod filter;
 mod init;
 
 #[cfg(feature = "init")]
-pub use init::init;
+pub use init::{init, init_from, init_from_env, init_with_level_colors, try_init};
+#[cfg(feature = "async")]
+pub use init::init_async;
 
+#[cfg(feature = "thread-local-buf")]
 thread_local! {
     static TL_BUF: RefCell<String> = RefCell::new(String::new())
 }
 
-/// `EnvLogger` drain.
-pub struct EnvLogger<T : Drain> {
-    drain : T,
-    directives: Vec<LogDirective>,
-    filter: Option<filter::Filter>,
-}
+/// Number of messages `LogBuilder::backoff_dedup` keeps track of
+#[cfg(feature = "std")]
+pub const BACKOFF_CAPACITY: usize = 256;
 
-/// LogBuilder acts as builder for initializing the EnvLogger.
-/// It can be used change the enviromental variable used
-/// to provide the logging directives and also set the default log level filter.
-pub struct LogBuilder<T : Drain> {
-    drain : T,
-    directives: Vec<LogDirective>,
-    filter: Option<filter::Filter>,
+#[cfg(feature = "std")]
+thread_local! {
+    /// Number of `with_silenced` calls running on this thread
+    static SILENCED: Cell<usize> = const { Cell::new(0) }
 }
 
-impl<T : Drain> LogBuilder<T> {
-    /// Initializes the log builder with defaults
-    pub fn new(d : T) -> Self {
-        LogBuilder {
-            drain : d,
-            directives: Vec::new(),
-            filter: None,
-        }
-    }
+#[cfg(feature = "std")]
+thread_local! {
+    /// Loggers installed with `LogBuilder::install_thread_local`, with the
+    /// id of their guard, the last one active
+    static THREAD_LOGGERS: RefCell<Vec<(u64, ThreadLogger)>> = const { RefCell::new(Vec::new()) }
+}
 
-    /// Adds filters to the logger
-    ///
-    /// The given module (if any) will log at most the specified level provided.
-    /// If no module is provided then the filter will apply to all log messages.
-    pub fn filter(mut self,
-                  module: Option<&str>,
-                  level: FilterLevel) -> Self {
-        self.directives.push(LogDirective {
-            name: module.map(|s| s.to_string()),
-            level: level,
-        });
-        self
-    }
+#[cfg(feature = "std")]
+type ThreadLogger = Arc<dyn Drain<Ok = (), Err = Never>>;
 
-    /// Parses the directives string in the same form as the RUST_LOG
-    /// environment variable.
-    ///
-    /// See the module documentation for more details.
-    pub fn parse(mut self, filters: &str) -> Self {
-        let (directives, filter) = parse_logging_spec(filters);
+/// Id of the next `ThreadGuard`
+#[cfg(feature = "std")]
+static NEXT_GUARD: AtomicU64 = AtomicU64::new(0);
 
-        self.filter = filter;
+/// Ends a `with_silenced` call, even if its closure panics
+#[cfg(feature = "std")]
+struct SilenceGuard;
 
-        for directive in directives {
-            self.directives.push(directive);
-        }
-        self
+#[cfg(feature = "std")]
+impl Drop for SilenceGuard {
+    fn drop(&mut self) {
+        SILENCED.with(|silenced| silenced.set(silenced.get() - 1));
     }
+}
 
-    /// Build an env logger.
-    pub fn build(mut self) -> EnvLogger<T> {
-        if self.directives.is_empty() {
-            // Adds the default filter if none exist
-            self.directives.push(LogDirective {
-                name: None,
-                level: FilterLevel::Error,
-            });
-        } else {
-            // Sort the directives by length of their name, this allows a
-            // little more efficient lookup at runtime.
-            self.directives.sort_by(|a, b| {
-                let alen = a.name.as_ref().map(|a| a.len()).unwrap_or(0);
-                let blen = b.name.as_ref().map(|b| b.len()).unwrap_or(0);
-                alen.cmp(&blen)
-            });
+/// Run `f` with an empty buffer to format into
+///
+/// With the `thread-local-buf` feature the buffer is reused between calls
+/// on the same thread.
+#[cfg(feature = "thread-local-buf")]
+fn with_buf<F: FnOnce(&mut String) -> R, R>(f: F) -> R {
+    TL_BUF.with(|buf| {
+        match buf.try_borrow_mut() {
+            Ok(mut buf) => {
+                buf.clear();
+                f(&mut buf)
+            },
+            // Formatting a message logged from within another one's formatting
+            Err(_) => f(&mut String::new()),
         }
+    })
+}
 
-        let LogBuilder {
-            drain,
-            directives,
-            filter,
-        } = self;
-
-        EnvLogger {
-            drain: drain,
-            directives: directives,
-            filter: filter,
-        }
-    }
+#[cfg(all(feature = "std", not(feature = "thread-local-buf")))]
+fn with_buf<F: FnOnce(&mut String) -> R, R>(f: F) -> R {
+    f(&mut String::new())
 }
 
-impl<T : Drain> EnvLogger<T> {
-    pub fn new(d : T) -> Self {
-        let mut builder = LogBuilder::new(d);
+/// Buffer messages are formatted into for filtering, see
+/// `LogBuilder::build_with_buf`
+#[cfg(feature = "std")]
+pub trait MessageBuf: Write {
+    /// Run `f` with an empty buffer
+    ///
+    /// As `f` may log, and so call this again before returning, buffers
+    /// reused between calls must not be handed out twice at once.
+    fn with_buf<F: FnOnce(&mut Self) -> R, R>(f: F) -> R;
 
-        if let Ok(s) = env::var("RUST_LOG") {
-            builder = builder.parse(&s);
-        }
+    /// The text written into the buffer
+    fn as_str(&self) -> &str;
+
+    /// Keep only the characters for which `f` returns `true`
+    fn retain<F: FnMut(char) -> bool>(&mut self, f: F);
+}
 
-        builder.build()
+/// Reused between calls on the same thread with the `thread-local-buf`
+/// feature, a new one otherwise
+#[cfg(feature = "std")]
+impl MessageBuf for String {
+    fn with_buf<F: FnOnce(&mut Self) -> R, R>(f: F) -> R {
+        with_buf(f)
     }
 
-    pub fn filter(&self) -> FilterLevel {
-        self.directives.iter()
-            .map(|d| d.level).max()
-            .unwrap_or(FilterLevel::Off)
+    fn as_str(&self) -> &str {
+        self
     }
 
-    fn enabled(&self, level: Level, module: &str) -> bool {
-        // Search for the longest match, the vector is assumed to be pre-sorted.
-        for directive in self.directives.iter().rev() {
-            match directive.name {
-                Some(ref name) if !module.starts_with(&**name) => {},
-                Some(..) | None => {
-                    return level.as_usize() <= directive.level.as_usize()
-                }
-            }
-        }
-        false
+    fn retain<F: FnMut(char) -> bool>(&mut self, f: F) {
+        String::retain(self, f)
     }
 }
 
-impl<T : Drain> Drain for EnvLogger<T>
-where T : Drain<Ok=()> {
-    type Err = T::Err;
-    type Ok = ();
-    fn log(&self, info: &Record, val : &OwnedKVList) -> result::Result<(), T::Err> {
-        if !self.enabled(info.level(), info.module()) {
-            return Ok(());
-        }
+/// Run `f` with the record's message, formatted into a buffer
+#[cfg(feature = "std")]
+fn with_msg<B: MessageBuf, F: FnOnce(&str) -> R, R>(info: &Record, f: F) -> R {
+    B::with_buf(|buf| {
+        let _ = write!(buf, "{}", info.msg());
+        f(buf.as_str())
+    })
+}
+
+/// Remove ANSI escape sequences, eg. colors, from `buf`
+#[cfg(feature = "std")]
+fn strip_ansi<B: MessageBuf>(buf: &mut B) {
+    enum State {
+        Text,
+        Escape,
+        // Control sequence, eg. `\x1b[31m`, ended by a letter or similar
+        Csi,
+        // Operating system command, ended by BEL or `\x1b\\`
+        Osc,
+    }
 
-        if let Some(filter) = self.filter.as_ref() {
-            if !filter.is_match(&format!("{}", info.msg())) {
-                return Ok(())
+    let mut state = State::Text;
+    buf.retain(|c| match state {
+        State::Text if c == '\x1b' => {
+            state = State::Escape;
+            false
+        },
+        State::Text => true,
+        State::Escape => {
+            state = match c {
+                '[' => State::Csi,
+                ']' => State::Osc,
+                _ => State::Text,
+            };
+            false
+        },
+        State::Csi => {
+            if ('\x40'..='\x7e').contains(&c) {
+                state = State::Text;
             }
-        }
+            false
+        },
+        State::Osc => {
+            match c {
+                '\x07' => state = State::Text,
+                '\x1b' => state = State::Escape,
+                _ => {},
+            }
+            false
+        },
+    });
+}
 
-        TL_BUF.with(|buf| {
-            let mut buf = buf.borrow_mut();
-            let res = self.drain.log(info, val);
-            buf.clear();
-            res
-        })
-    }
+/// Type-erased drain, used for secondary outputs
+#[cfg(feature = "std")]
+type BoxedDrain<E> = Box<dyn Drain<Ok = (), Err = E> + Send + Sync + RefUnwindSafe + UnwindSafe>;
+
+/// Type-erased message transformation, see `LogBuilder::map_message`
+#[cfg(feature = "std")]
+type MessageMap = Box<dyn Fn(&str) -> Cow<str> + Send + Sync + RefUnwindSafe + UnwindSafe>;
+
+/// Directives applied on top of the configured ones until a deadline
+#[cfg(feature = "std")]
+struct Elevation {
+    directives: Vec<LogDirective>,
+    until: Instant,
 }
 
-struct LogDirective {
-    name: Option<String>,
-    level: FilterLevel,
+/// Counts of the filtering decisions, see `EnvLogger::metrics`
+///
+/// Meant to be exported, eg. as `envlogger_records_passed_total` and
+/// `envlogger_records_dropped_total{reason="level|filter"}`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Records passed on to the wrapped drain, or a route
+    pub passed: u64,
+    /// Records dropped by the directives
+    pub dropped_level: u64,
+    /// Records dropped by the filter, including ones sent to the
+    /// `filter_miss_drain`
+    pub dropped_filter: u64,
 }
 
-/// Create a `EnvLogger` using `RUST_LOG` environment variable
-pub fn new<T : Drain>(d : T) -> EnvLogger<T> {
-    let mut builder = LogBuilder::new(d);
-
-    if let Ok(s) = env::var("RUST_LOG") {
-        builder = builder.parse(&s);
-    }
-
-    builder.build()
-}
-
-/// Parse a logging specification string (e.g: "crate1,crate2::mod3,crate3::x=error/foo")
-/// and return a vector with log directives.
-fn parse_logging_spec(spec: &str) -> (Vec<LogDirective>, Option<filter::Filter>) {
-    let mut dirs = Vec::new();
-
-    let mut parts = spec.split('/');
-    let mods = parts.next();
-    let filter = parts.next();
-    if parts.next().is_some() {
-        println!("warning: invalid logging spec '{}', \
-                 ignoring it (too many '/'s)", spec);
-        return (dirs, None);
-    }
-    mods.map(|m| { for s in m.split(',') {
-        if s.len() == 0 { continue }
-        let mut parts = s.split('=');
-        let (log_level, name) = match (parts.next(), parts.next().map(|s| s.trim()), parts.next()) {
-            (Some(part0), None, None) => {
-                // if the single argument is a log-level string or number,
-                // treat that as a global fallback
-                match part0.parse() {
-                    Ok(num) => (num, None),
-                    Err(_) => (FilterLevel::max(), Some(part0)),
+/// Why records are logged or not, see `EnvLogger::explain`
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    /// The directive deciding the level, with its position in the order the
+    /// directives are listed with `RUST_LOG_DEBUG`, or `None` if none matched
+    pub directive: Option<(usize, LogDirective)>,
+    /// Whether the level is enabled, same as `EnvLogger::would_log`
+    pub enabled: bool,
+    /// Whether records are still checked against a filter (the regex, length,
+    /// key or error filter)
+    pub filtered: bool,
+}
+
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct MetricCounters {
+    passed: AtomicU64,
+    dropped_level: AtomicU64,
+    dropped_filter: AtomicU64,
+}
+
+/// How records from a module are rendered, see `LogBuilder::format_for`
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatKind {
+    /// Just the message, dropping all key-values
+    Compact,
+    /// The message followed by all key-values, eg. `msg key=value`
+    Full,
+    /// The message and all key-values as JSON object, eg.
+    /// `{"msg":"msg","key":"value"}`
+    Json,
+}
+
+#[cfg(feature = "std")]
+impl FormatKind {
+    fn render(&self, info: &Record, val: &OwnedKVList) -> String {
+        let mut line = String::new();
+        match *self {
+            FormatKind::Compact => {
+                let _ = write!(line, "{}", info.msg());
+            },
+            FormatKind::Full => {
+                let _ = write!(line, "{}", info.msg());
+                for (key, value) in kv::collect(info, val) {
+                    let _ = write!(line, " {}={}", key, value);
                 }
-            }
-            (Some(part0), Some(""), None) => (FilterLevel::max(), Some(part0)),
-            (Some(part0), Some(part1), None) => {
-                match part1.parse() {
-                    Ok(num) => (num, Some(part0)),
-                    _ => {
-                        println!("warning: invalid logging spec '{}', \
-                                 ignoring it", part1);
-                        continue
-                    }
+            },
+            FormatKind::Json => {
+                line.push_str("{\"msg\":");
+                push_json_str(&mut line, &info.msg().to_string());
+                for (key, value) in kv::collect(info, val) {
+                    line.push(',');
+                    push_json_str(&mut line, &key);
+                    line.push(':');
+                    push_json_str(&mut line, &value);
                 }
+                line.push('}');
             },
-            _ => {
-                println!("warning: invalid logging spec '{}', \
-                         ignoring it", s);
-                continue
-            }
-        };
-        dirs.push(LogDirective {
-            name: name.map(|s| s.to_string()),
-            level: log_level,
-        });
-    }});
+        }
+        line
+    }
+}
 
-    let filter = filter.map_or(None, |filter| {
-        match filter::Filter::new(filter) {
-            Ok(re) => Some(re),
-            Err(e) => {
-                println!("warning: invalid regex filter - {}", e);
-                None
-            }
+/// Append `s` to `buf` as JSON string
+#[cfg(feature = "std")]
+fn push_json_str(buf: &mut String, s: &str) {
+    buf.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(buf, "\\u{:04x}", c as u32);
+            },
+            c => buf.push(c),
         }
-    });
+    }
+    buf.push('"');
+}
 
-    return (dirs, filter);
+/// What the filter is matched against
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FilterTarget {
+    /// The formatted message
+    #[default]
+    Message,
+    /// The message prefixed with the record's module, as `module: message`,
+    /// eg. `/::http: .*timeout` matches timeouts only from `http` modules
+    QualifiedMessage,
+    /// The record's module alone, eg. `/::(db|cache)$` matches records from
+    /// all `db` and `cache` modules, whatever their crate
+    Module,
 }
 
-#[cfg(test)]
-mod tests {
-    use slog::{Level, FilterLevel};
-    use super::slog;
+/// How records with an empty module, eg. from custom macros, are handled
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyModulePolicy {
+    /// Only global directives apply, as if no module directive matched
+    #[default]
+    TreatAsGlobal,
+    /// Never log them, whatever the directives and floor
+    AlwaysSuppress,
+    /// Always log them, whatever the directives
+    AlwaysLog,
+}
 
-    use super::{LogBuilder, EnvLogger, LogDirective, parse_logging_spec};
+/// What the filter decides for messages longer than `LogBuilder::max_filter_len`
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OversizedPolicy {
+    /// They pass, as if the filter matched
+    #[default]
+    Pass,
+    /// They're dropped, as if the filter didn't match
+    Drop,
+}
 
-    fn make_logger(dirs: Vec<LogDirective>) -> EnvLogger<slog::Discard> {
-        let mut logger = LogBuilder::new(slog::Discard).build();
-        logger.directives = dirs;
-        logger
-    }
+/// How the directive deciding a record's level is picked
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// The directive with the longest module prefix matching the record's
+    /// module, eg. `crate1::mod1=warn` wins over `crate1=debug`
+    #[default]
+    LongestPrefix,
+    /// The last directive matching the record's module, regardless of its
+    /// length, eg. `crate1::mod1=warn,crate1=debug` logs debug records of
+    /// `crate1::mod1`
+    LastWins,
+}
 
-    #[test]
-    fn filter_info() {
-        let logger = LogBuilder::new(slog::Discard).filter(None, FilterLevel::Info).build();
-        assert!(logger.enabled(Level::Info, "crate1"));
-        assert!(!logger.enabled(Level::Debug, "crate1"));
-    }
+/// `EnvLogger` drain.
+///
+/// It's transparent to the wrapped drain's types: its `Drain::Err` is
+/// `T::Err`, and errors of the wrapped drain, or of secondary drains like
+/// routes, are returned unchanged. Its `Drain::Ok` is `T::Ok`, which has to
+/// implement `Default`, for records that aren't passed on.
+///
+/// An `EnvLogger` is `Send` and `Sync`, as well as unwind safe, whenever its
+/// drain is, so it can be shared across threads in an `Arc`. All its state
+/// that changes while logging (counters, caches, reloaded directives) is kept
+/// in atomics or behind a `Mutex`.
+#[cfg(feature = "std")]
+pub struct EnvLogger<T : Drain, B = String> {
+    drain : T,
+    directives: Vec<LogDirective>,
+    kv_directives: Vec<KvDirective>,
+    // Thread names with their level
+    thread_directives: Vec<(String, FilterLevel)>,
+    // Directives with their remaining number of records, sorted like `directives`
+    counted_directives: Vec<(LogDirective, AtomicU64)>,
+    filter: Option<filter::Filter>,
+    // Applies when there's no `filter`
+    default_filter: Option<filter::Filter>,
+    filter_bound: Option<FilterBound>,
+    len_filter: Option<LenFilter>,
+    key_filter: Option<KeyPresenceFilter>,
+    kv_threshold: Option<KvThresholdFilter>,
+    level_filters: Vec<(Level, Option<filter::Filter>)>,
+    errors_only: bool,
+    case_insensitive: bool,
+    boundary_aware: bool,
+    strip_ansi: bool,
+    prefer_quieter: bool,
+    allowlist: Option<Vec<String>>,
+    match_mode: MatchMode,
+    empty_module_policy: EmptyModulePolicy,
+    filter_target: FilterTarget,
+    max_filter_len: Option<usize>,
+    oversized_policy: OversizedPolicy,
+    required_prefix: Option<String>,
+    sample: Option<u32>,
+    rate_limit: Option<u32>,
+    enabled_flag: Option<Arc<AtomicBool>>,
+    // Whether an error passed, with `stop_after_first_error`
+    stopped: Option<AtomicBool>,
+    min_floor: Option<Level>,
+    max_depth: Option<usize>,
+    level_override_key: Option<&'static str>,
+    // Only kept for `fork`
+    allow_comments: bool,
+    profiles: HashMap<String, String>,
+    warnings: Option<Arc<Mutex<Vec<String>>>>,
+    sampled: AtomicUsize,
+    // Nanoseconds since `created` at which the token bucket of the rate
+    // limit is full again
+    rate_full_at: AtomicU64,
+    rate_dropped: AtomicUsize,
+    // Hash of module and message, and occurrences, most recent first
+    backoff: Option<Mutex<VecDeque<(u64, u64)>>>,
+    annotate_match: bool,
+    filter_miss_drain: Option<BoxedDrain<T::Err>>,
+    audit_drain: Option<BoxedDrain<T::Err>>,
+    map_message: Option<MessageMap>,
+    routes: Vec<(String, BoxedDrain<T::Err>)>,
+    formats: Vec<(String, FormatKind)>,
+    memory_capacity: usize,
+    memory: Mutex<VecDeque<String>>,
+    summary_interval: Option<Duration>,
+    // Nanoseconds since `created` at which the next summary is due
+    summary_due: AtomicU64,
+    created: Instant,
+    // Records suppressed since the last summary, per module
+    suppressed: Mutex<HashMap<&'static str, u64>>,
+    metrics: MetricCounters,
+    elevated: AtomicBool,
+    elevation: Mutex<Option<Elevation>>,
+    shadowed: AtomicBool,
+    shadow: Mutex<Option<Vec<LogDirective>>>,
+    shadow_diffs: AtomicU64,
+    #[cfg(feature = "stats")]
+    stats: Mutex<HashMap<&'static str, u64>>,
+    // The glob directives of `directives`, if there are enough
+    #[cfg(feature = "globset")]
+    globs: Option<globs::GlobIndex>,
+    // Only ever read from, so a panic can't leave it inconsistent
+    #[cfg(feature = "tokio")]
+    watch: Option<AssertUnwindSafe<watch::Receiver<Directives>>>,
+    // Stops listening on the control socket when dropped
+    #[cfg(all(unix, feature = "control-socket"))]
+    control: Option<control::Listener>,
+    _buf: PhantomData<fn() -> B>,
+}
 
-    #[test]
-    fn filter_beginning_longest_match() {
-        let logger = LogBuilder::new(slog::Discard)
-                        .filter(Some("crate2"), FilterLevel::Info)
-                        .filter(Some("crate2::mod"), FilterLevel::Debug)
-                        .filter(Some("crate1::mod1"), FilterLevel::Warning)
-                        .build();
-        assert!(logger.enabled(Level::Debug, "crate2::mod1"));
-        assert!(!logger.enabled(Level::Debug, "crate2"));
+// Checks that no field breaks the thread safety promised above
+#[cfg(feature = "std")]
+const _: fn() = || {
+    fn assert_thread_safe<T: Send + Sync + RefUnwindSafe + UnwindSafe>() {}
+    fn assert_env_logger<T: Drain + Send + Sync + RefUnwindSafe + UnwindSafe>() {
+        assert_thread_safe::<EnvLogger<T>>();
     }
+    assert_env_logger::<slog::Discard>();
+};
 
-    #[test]
-    fn parse_default() {
-        let logger = LogBuilder::new(slog::Discard).parse("info,crate1::mod1=warn").build();
-        assert!(logger.enabled(Level::Warning, "crate1::mod1"));
-        assert!(logger.enabled(Level::Info, "crate2::mod2"));
-    }
+/// A set of module directives, sent to a logger built with
+/// `LogBuilder::build_with_watch` to replace its active ones
+#[cfg(feature = "tokio")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Directives {
+    directives: Vec<LogDirective>,
+}
 
-    #[test]
-    fn match_full_path() {
-        let logger = make_logger(vec![
-            LogDirective {
-                name: Some("crate2".to_string()),
-                level: FilterLevel::Info
-            },
-            LogDirective {
-                name: Some("crate1::mod1".to_string()),
-                level: FilterLevel::Warning
-            }
-        ]);
-        assert!(logger.enabled(Level::Warning, "crate1::mod1"));
-        assert!(!logger.enabled(Level::Info, "crate1::mod1"));
-        assert!(logger.enabled(Level::Info, "crate2"));
-        assert!(!logger.enabled(Level::Debug, "crate2"));
+#[cfg(feature = "tokio")]
+impl Directives {
+    /// Directives matched by longest prefix, like the ones of a built logger
+    pub fn new(mut directives: Vec<LogDirective>) -> Self {
+        sort_directives(&mut directives, MatchMode::LongestPrefix);
+        Directives { directives }
     }
 
-    #[test]
-    fn no_match() {
-        let logger = make_logger(vec![
-            LogDirective { name: Some("crate2".to_string()), level: FilterLevel::Info },
-            LogDirective { name: Some("crate1::mod1".to_string()), level: FilterLevel::Warning }
-        ]);
-        assert!(!logger.enabled(Level::Warning, "crate3"));
+    /// The module directives of `spec`
+    ///
+    /// Relative directives adjust the global level of `spec`, or `error`.
+    /// Anything else in the spec, like a filter, is ignored. Invalid parts
+    /// are skipped with a warning.
+    pub fn parse(spec: &str) -> Self {
+        let parsed = parse_spec(spec);
+        print_warnings(&parsed.errors);
+        Directives::new(parsed.resolved_directives(FilterLevel::Error))
     }
+}
 
-    #[test]
-    fn match_beginning() {
-        let logger = make_logger(vec![
-            LogDirective { name: Some("crate2".to_string()), level: FilterLevel::Info },
-            LogDirective { name: Some("crate1::mod1".to_string()), level: FilterLevel::Warning }
-        ]);
-        assert!(logger.enabled(Level::Info, "crate2::mod1"));
-    }
+/// Drain passing records to the logger installed on the current thread
+///
+/// See `LogBuilder::install_thread_local`. Records logged on threads without
+/// one are dropped.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadLocalDrain;
 
-    #[test]
-    fn match_beginning_longest_match() {
-        let logger = make_logger(vec![
-            LogDirective { name: Some("crate2".to_string()), level: FilterLevel::Info },
-            LogDirective { name: Some("crate2::mod".to_string()), level: FilterLevel::Debug },
-            LogDirective { name: Some("crate1::mod1".to_string()), level: FilterLevel::Warning }
-        ]);
-        assert!(logger.enabled(Level::Debug, "crate2::mod1"));
-        assert!(!logger.enabled(Level::Debug, "crate2"));
+#[cfg(feature = "std")]
+impl Drain for ThreadLocalDrain {
+    type Ok = ();
+    type Err = Never;
+
+    fn log(&self, info: &Record, val: &OwnedKVList) -> result::Result<(), Never> {
+        // Not borrowed while logging, so drains may install loggers or drop
+        // guards themselves
+        let logger = THREAD_LOGGERS.with(|loggers| loggers.borrow().last().map(|(_, logger)| logger.clone()));
+        match logger {
+            Some(logger) => logger.log(info, val),
+            None => Ok(()),
+        }
     }
+}
 
-    #[test]
-    fn match_default() {
-        let logger = make_logger(vec![
-            LogDirective { name: None, level: FilterLevel::Info },
-            LogDirective { name: Some("crate1::mod1".to_string()), level: FilterLevel::Warning }
-        ]);
-        assert!(logger.enabled(Level::Warning, "crate1::mod1"));
-        assert!(logger.enabled(Level::Info, "crate2::mod2"));
+/// Uninstalls a logger installed with `LogBuilder::install_thread_local`
+/// when dropped
+///
+/// If it was the active logger, the one installed before it on this thread,
+/// if any, is active again. Guards may be dropped in any order, each only
+/// uninstalls its own logger.
+#[cfg(feature = "std")]
+#[must_use = "the logger is uninstalled when the guard is dropped"]
+pub struct ThreadGuard {
+    id: u64,
+    // Bound to the thread the logger was installed on
+    _thread: PhantomData<*const ()>,
+}
+
+#[cfg(feature = "std")]
+impl Drop for ThreadGuard {
+    fn drop(&mut self) {
+        // Thread-locals may already be gone at thread exit
+        let removed = THREAD_LOGGERS.try_with(|loggers| {
+            let mut loggers = loggers.borrow_mut();
+            let i = loggers.iter().rposition(|&(id, _)| id == self.id);
+            i.map(|i| loggers.remove(i))
+        });
+        // The logger is dropped once the list isn't borrowed anymore
+        drop(removed);
+    }
+}
+
+/// LogBuilder acts as builder for initializing the EnvLogger.
+/// It can be used change the enviromental variable used
+/// to provide the logging directives and also set the default log level filter.
+#[cfg(feature = "std")]
+pub struct LogBuilder<T : Drain> {
+    drain : T,
+    directives: Vec<LogDirective>,
+    kv_directives: Vec<KvDirective>,
+    thread_directives: Vec<(String, FilterLevel)>,
+    relative_directives: Vec<RelativeDirective>,
+    no_default: bool,
+    counted_directives: Vec<(LogDirective, u64)>,
+    filter: Option<filter::Filter>,
+    default_filter: Option<filter::Filter>,
+    filter_bound: Option<FilterBound>,
+    len_filter: Option<LenFilter>,
+    key_filter: Option<KeyPresenceFilter>,
+    kv_threshold: Option<KvThresholdFilter>,
+    level_filters: Vec<(Level, Option<filter::Filter>)>,
+    errors_only: bool,
+    case_insensitive: bool,
+    boundary_aware: bool,
+    strip_ansi: bool,
+    prefer_quieter: bool,
+    allowlist: Option<Vec<String>>,
+    match_mode: MatchMode,
+    empty_module_policy: EmptyModulePolicy,
+    filter_target: FilterTarget,
+    max_filter_len: Option<usize>,
+    oversized_policy: OversizedPolicy,
+    required_prefix: Option<String>,
+    sample: Option<u32>,
+    rate_limit: Option<u32>,
+    backoff_dedup: bool,
+    annotate_match: bool,
+    enabled_flag: Option<Arc<AtomicBool>>,
+    stop_after_first_error: bool,
+    min_floor: Option<Level>,
+    max_depth: Option<usize>,
+    level_override_key: Option<&'static str>,
+    allow_comments: bool,
+    profiles: HashMap<String, String>,
+    lazy_filter: bool,
+    filter_miss_drain: Option<BoxedDrain<T::Err>>,
+    audit_drain: Option<BoxedDrain<T::Err>>,
+    map_message: Option<MessageMap>,
+    routes: Vec<(String, BoxedDrain<T::Err>)>,
+    formats: Vec<(String, FormatKind)>,
+    memory_capacity: usize,
+    summary_interval: Option<Duration>,
+    warnings: Option<Arc<Mutex<Vec<String>>>>,
+    #[cfg(all(unix, feature = "control-socket"))]
+    control_socket: Option<PathBuf>,
+}
+
+/// Where to read the logging spec from
+///
+/// Mirrors `env_logger::Env`, to ease porting: the spec is read from
+/// `RUST_LOG` (or the variable set with `filter`), falling back to the
+/// default set with `default_filter_or` when the variable is unset.
+///
+/// Additionally, a level floor (see `LogBuilder::min_floor`) is read from
+/// `RUST_LOG_MIN`, eg. `RUST_LOG_MIN=error`, and `RUST_LOG_DEBUG=1` prints
+/// the effective configuration of loggers created by `EnvLogger::from_env`.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Env {
+    var: String,
+    default: Option<String>,
+    floor_var: Option<String>,
+    debug_var: Option<String>,
+}
+
+#[cfg(feature = "std")]
+impl Default for Env {
+    fn default() -> Self {
+        Env {
+            var: "RUST_LOG".to_string(),
+            default: None,
+            floor_var: Some("RUST_LOG_MIN".to_string()),
+            debug_var: Some("RUST_LOG_DEBUG".to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Env {
+    /// Read the spec from `RUST_LOG`, without a default
+    pub fn new() -> Self {
+        Env::default()
+    }
+
+    /// Read the spec from `var`, and no other variable
+    ///
+    /// Unlike `Env::new().filter(var)`, neither a floor nor whether to print
+    /// the configuration is read, for environments where reading arbitrary
+    /// variables is a concern.
+    pub fn only(var: &str) -> Self {
+        Env {
+            var: var.to_string(),
+            default: None,
+            floor_var: None,
+            debug_var: None,
+        }
+    }
+
+    /// Read the spec from `var` instead of `RUST_LOG`
+    pub fn filter(mut self, var: &str) -> Self {
+        self.var = var.to_string();
+        self
+    }
+
+    /// Use `default` as the spec if the variable is unset
+    pub fn default_filter_or(mut self, default: &str) -> Self {
+        self.default = Some(default.to_string());
+        self
+    }
+
+    /// Read the level floor from `var` instead of `RUST_LOG_MIN`
+    pub fn floor(mut self, var: &str) -> Self {
+        self.floor_var = Some(var.to_string());
+        self
+    }
+
+    /// Read whether to print the configuration from `var` instead of
+    /// `RUST_LOG_DEBUG`
+    pub fn debug(mut self, var: &str) -> Self {
+        self.debug_var = Some(var.to_string());
+        self
+    }
+
+    /// The spec to use, if any
+    pub fn get_filter(&self) -> Option<String> {
+        env::var(&self.var).ok().or_else(|| self.default.clone())
+    }
+
+    /// The level floor to use, if the variable is set to a valid level
+    pub fn get_floor(&self) -> Option<Level> {
+        let level = env::var(self.floor_var.as_ref()?).ok()?;
+        parse::parse_record_level(&level)
+    }
+
+    /// Whether to print the configuration, ie. the variable is set to
+    /// anything but an empty string, `0` or `false`
+    pub fn get_debug(&self) -> bool {
+        self.debug_var.as_ref().and_then(|var| env::var(var).ok())
+            .is_some_and(|debug| !matches!(debug.trim(), "" | "0" | "false"))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> From<&'a str> for Env {
+    fn from(var: &'a str) -> Self {
+        Env::new().filter(var)
+    }
+}
+
+#[cfg(feature = "std")]
+impl LogBuilder<Discard> {
+    /// Initializes a log builder with `dirs`, discarding all records
+    ///
+    /// Meant for testing directive configurations with
+    /// `EnvLogger::would_log`.
+    pub fn from_directives(dirs: Vec<LogDirective>) -> Self {
+        let mut builder = LogBuilder::new(Discard);
+        builder.directives = dirs;
+        builder
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T : Drain> LogBuilder<T> {
+    /// Initializes the log builder with the spec from `env`
+    pub fn from_env<E: Into<Env>>(d: T, env: E) -> Self {
+        LogBuilder::new(d).apply_env(&env.into())
+    }
+
+    /// Apply the floor and the spec from `env`
+    fn apply_env(mut self, env: &Env) -> Self {
+        if let Some(floor) = env.get_floor() {
+            self = self.min_floor(floor);
+        }
+        match env.get_filter() {
+            Some(s) => self.parse(&s),
+            None => self,
+        }
+    }
+
+    /// Initializes the log builder with the spec from `RUST_LOG`
+    pub fn from_default_env(d: T) -> Self {
+        LogBuilder::from_env(d, Env::default())
+    }
+
+    /// Initializes the log builder with defaults
+    pub fn new(d : T) -> Self {
+        LogBuilder {
+            drain : d,
+            directives: Vec::new(),
+            kv_directives: Vec::new(),
+            thread_directives: Vec::new(),
+            relative_directives: Vec::new(),
+            no_default: false,
+            counted_directives: Vec::new(),
+            filter: None,
+            default_filter: None,
+            filter_bound: None,
+            len_filter: None,
+            key_filter: None,
+            kv_threshold: None,
+            level_filters: Vec::new(),
+            errors_only: false,
+            case_insensitive: false,
+            boundary_aware: false,
+            strip_ansi: false,
+            prefer_quieter: false,
+            allowlist: None,
+            match_mode: MatchMode::LongestPrefix,
+            empty_module_policy: EmptyModulePolicy::TreatAsGlobal,
+            filter_target: FilterTarget::Message,
+            max_filter_len: None,
+            oversized_policy: OversizedPolicy::Pass,
+            required_prefix: None,
+            sample: None,
+            rate_limit: None,
+            backoff_dedup: false,
+            annotate_match: false,
+            enabled_flag: None,
+            stop_after_first_error: false,
+            min_floor: None,
+            max_depth: None,
+            level_override_key: None,
+            allow_comments: false,
+            profiles: HashMap::new(),
+            lazy_filter: false,
+            filter_miss_drain: None,
+            audit_drain: None,
+            map_message: None,
+            routes: Vec::new(),
+            formats: Vec::new(),
+            memory_capacity: 0,
+            summary_interval: None,
+            warnings: None,
+            #[cfg(all(unix, feature = "control-socket"))]
+            control_socket: None,
+        }
+    }
+
+    /// Initializes the log builder with room for `n` directives
+    ///
+    /// Saves reallocations when adding many directives programmatically,
+    /// eg. with `filter`.
+    pub fn with_capacity(d: T, n: usize) -> Self {
+        let mut builder = LogBuilder::new(d);
+        builder.directives.reserve(n);
+        builder
+    }
+
+    /// Compare module names against directives case-insensitively
+    ///
+    /// Names are compared by their Unicode lowercase form, so `MyCrate=debug`
+    /// matches records from module `mycrate`, and `Straße=debug` those from
+    /// `STRAẞE`. Matching is case-sensitive by default.
+    pub fn case_insensitive_modules(mut self) -> Self {
+        self.case_insensitive = true;
+        self
+    }
+
+    /// Only match directives against whole module path segments
+    ///
+    /// By default a directive matches any module starting with its name, so
+    /// `app=debug` also enables `apple`. With this, the name must be followed
+    /// by `::` or the end of the module path.
+    ///
+    /// A name ending with `_` is taken as a crate name prefix instead, and
+    /// still matches textually: `myorg_=debug` enables all crates of a
+    /// workspace named like `myorg_foo` and `myorg_bar`.
+    pub fn boundary_aware(mut self) -> Self {
+        self.boundary_aware = true;
+        self
+    }
+
+    /// Only ever log records from `modules`, at up to `level`
+    ///
+    /// Adds a directive for each module, and suppresses all other modules,
+    /// whatever other directives, key-value directives, elevations or the
+    /// floor say. Unlike an `off` global directive, a more specific
+    /// directive can't enable an unlisted module by accident. Can be called
+    /// several times to extend the list.
+    pub fn allowlist(mut self, modules: &[&str], level: FilterLevel) -> Self {
+        let allowlist = self.allowlist.get_or_insert_with(Vec::new);
+        for module in modules {
+            allowlist.push(module.to_string());
+            self.directives.push(LogDirective::new(Some(module), level));
+        }
+        self
+    }
+
+    /// Break ties between equally specific directives towards the quieter
+    ///
+    /// If several directives with a `ModuleMatcher` match a module, the one
+    /// enabling the most verbose level wins by default, so ambiguous
+    /// configurations err on showing more. With this, the least verbose one
+    /// wins instead. Doesn't apply with `MatchMode::LastWins`.
+    pub fn prefer_quieter(mut self) -> Self {
+        self.prefer_quieter = true;
+        self
+    }
+
+    /// Set how the directive deciding a record's level is picked
+    ///
+    /// Defaults to `MatchMode::LongestPrefix`. With `MatchMode::LastWins`,
+    /// directives keep the order they were added or parsed in.
+    pub fn match_mode(mut self, mode: MatchMode) -> Self {
+        self.match_mode = mode;
+        self
+    }
+
+    /// Don't match the filter against messages longer than `bytes`
+    ///
+    /// Bounds the cost of matching the filter, which is rarely meant for big
+    /// messages like dumps. What the filter decides for those instead is set
+    /// with `oversized_policy`, by default they pass. Other filters, like the
+    /// length filter, still apply. By default messages of any length are
+    /// matched.
+    pub fn max_filter_len(mut self, bytes: usize) -> Self {
+        self.max_filter_len = Some(bytes);
+        self
+    }
+
+    /// Set what the filter decides for messages over `max_filter_len`
+    ///
+    /// Defaults to `OversizedPolicy::Pass`.
+    pub fn oversized_policy(mut self, policy: OversizedPolicy) -> Self {
+        self.oversized_policy = policy;
+        self
+    }
+
+    /// Only log messages starting with `prefix`
+    ///
+    /// A coarse filter, cheaper than a regex, for messages tagged to stand
+    /// out, eg. `require_prefix("[DEBUG-ME]")`. It's checked on the message
+    /// alone, whatever the filter target, and both it and the filter have to
+    /// pass. Like other filters, it's bounded by `filter_bound`.
+    pub fn require_prefix(mut self, prefix: &str) -> Self {
+        self.required_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Set how records with an empty module are handled
+    ///
+    /// Defaults to `EmptyModulePolicy::TreatAsGlobal`.
+    pub fn empty_module_policy(mut self, policy: EmptyModulePolicy) -> Self {
+        self.empty_module_policy = policy;
+        self
+    }
+
+    /// Only apply the filter to records within `bound`
+    ///
+    /// Records outside of it pass regardless of the filter. Can also be set
+    /// with a `@` suffix of the filter, eg. `/foo@<info`.
+    pub fn filter_bound(mut self, bound: FilterBound) -> Self {
+        self.filter_bound = Some(bound);
+        self
+    }
+
+    /// Use `pattern` as filter, unless a parsed spec includes one
+    ///
+    /// Lets a binary ship with a default filter, that can be overridden
+    /// with a `/filter` in `RUST_LOG`. Like a parsed filter, it's ignored
+    /// with the `filter-disable=true` option.
+    pub fn default_filter(mut self, pattern: &str) -> Self {
+        self.default_filter = Some(filter::Filter::lazy(pattern));
+        self
+    }
+
+    /// Set what the filter is matched against
+    ///
+    /// Defaults to `FilterTarget::Message`. A length filter always applies
+    /// to the message alone.
+    pub fn filter_target(mut self, target: FilterTarget) -> Self {
+        self.filter_target = target;
+        self
+    }
+
+    /// Remove ANSI escape sequences from messages before filtering them
+    ///
+    /// For libraries logging colored messages, which filters expecting plain
+    /// text wouldn't match. The length filter then counts the visible text
+    /// only. Messages are still forwarded unchanged.
+    pub fn strip_ansi_for_filter(mut self) -> Self {
+        self.strip_ansi = true;
+        self
+    }
+
+    /// Only log records whose formatted message length passes `len_filter`
+    ///
+    /// If there's also a filter, both have to match. Can also be set with a
+    /// `@len` suffix of the filter, eg. `/@len>1000`.
+    pub fn len_filter(mut self, len_filter: LenFilter) -> Self {
+        self.len_filter = Some(len_filter);
+        self
+    }
+
+    /// Only log records carrying the key of `key_filter`
+    ///
+    /// If there's also a filter, both have to match. Can also be set with a
+    /// `@has:` suffix of the filter, eg. `/@has:trace_id`.
+    pub fn key_filter(mut self, key_filter: KeyPresenceFilter) -> Self {
+        self.key_filter = Some(key_filter);
+        self
+    }
+
+    /// Only log records whose numeric key-value passes `kv_threshold`
+    ///
+    /// For logging only slow operations, say. Records without the key, or
+    /// whose value doesn't format as a number, don't pass. If there's also a
+    /// filter, both have to match. Can also be set with a `@key` suffix of
+    /// the filter, eg. `/@duration_ms>500`.
+    pub fn kv_threshold(mut self, kv_threshold: KvThresholdFilter) -> Self {
+        self.kv_threshold = Some(kv_threshold);
+        self
+    }
+
+    /// Use `pattern` as the filter for records of `level`, or none if `None`
+    ///
+    /// Replaces the filter for that level only, eg. to filter debug records
+    /// by subsystem while letting all warnings through. Can also be set with
+    /// a `;level/pattern` suffix of the spec, eg. `debug/db;warn/`.
+    pub fn level_filter(mut self, level: Level, pattern: Option<&str>) -> Self {
+        self.level_filters.push((level, pattern.map(filter::Filter::lazy)));
+        self
+    }
+
+    /// Only log records carrying an error value
+    ///
+    /// Errors are key-values logged with `#`, eg. `"err" => #e`, on the
+    /// record or the logger it's logged through. If there's also a filter,
+    /// both have to match. Can also be set with a `@errors` suffix of the
+    /// filter, eg. `/@errors`.
+    pub fn errors_only(mut self) -> Self {
+        self.errors_only = true;
+        self
+    }
+
+    /// Send records that are enabled, but don't match the filter, to `d`
+    ///
+    /// This allows eg. keeping a complete log file, while the main drain only
+    /// gets the records matching the filter. Errors of `d` are returned just
+    /// like the ones of the main drain.
+    pub fn filter_miss_drain<R>(mut self, d: R) -> Self
+    where R: Drain<Ok = (), Err = T::Err> + Send + Sync + RefUnwindSafe + UnwindSafe + 'static {
+        self.filter_miss_drain = Some(Box::new(d));
+        self
+    }
+
+    /// Send a trace of every record dropped by the directives to `d`
+    ///
+    /// For proof of what was filtered out, without keeping what it said: `d`
+    /// gets records with the level, location and tag of the dropped ones, an
+    /// empty message and only a `ts` key-value, the time they were dropped
+    /// at in milliseconds since the Unix epoch. Neither the key-values of the
+    /// record nor the ones of its logger are passed on. Errors of `d` are
+    /// returned just like the ones of the main drain.
+    pub fn audit_drain<A>(mut self, d: A) -> Self
+    where A: Drain<Ok = (), Err = T::Err> + Send + Sync + RefUnwindSafe + UnwindSafe + 'static {
+        self.audit_drain = Some(Box::new(d));
+        self
+    }
+
+    /// Transform the messages of records that pass filtering
+    ///
+    /// Records are forwarded with the message returned by `f`, eg. to redact
+    /// secrets. Filtering still sees the original message. As messages of
+    /// `Record`s can't be changed, a new record is built, with the same
+    /// level, location, tag and key-values.
+    pub fn map_message<F>(mut self, f: F) -> Self
+    where F: Fn(&str) -> Cow<str> + Send + Sync + RefUnwindSafe + UnwindSafe + 'static {
+        self.map_message = Some(Box::new(f));
+        self
+    }
+
+    /// Send records from `module` to `drain` instead of the main drain
+    ///
+    /// Like directives, `module` is a prefix, and the route with the longest
+    /// matching one is taken. Records from modules without a route go to the
+    /// main drain. Only records that pass filtering are routed.
+    pub fn route<R>(mut self, module: &str, drain: R) -> Self
+    where R: Drain<Ok = (), Err = T::Err> + Send + Sync + RefUnwindSafe + UnwindSafe + 'static {
+        self.routes.push((module.to_string(), Box::new(drain)));
+        self
+    }
+
+    /// Render records from `module` as `format` before passing them on
+    ///
+    /// The rendered record has the same level and location, but the
+    /// rendering as message and no key-values, so the drain's format
+    /// applies around it. Like routes, the format with the longest module
+    /// prefix is taken, and records from other modules are passed on as
+    /// they are.
+    pub fn format_for(mut self, module: &str, format: FormatKind) -> Self {
+        self.formats.push((module.to_string(), format));
+        self
+    }
+
+    /// Only log every `n`-th record that passes filtering
+    ///
+    /// `0` and `1` log every record. Can also be set with the `sample=N`
+    /// option of the spec.
+    pub fn sample(mut self, n: u32) -> Self {
+        self.sample = if n > 1 { Some(n) } else { None };
+        self
+    }
+
+    /// Drop all records while `flag` is `false`
+    ///
+    /// A global switch that can be flipped from anywhere in the program with
+    /// a single atomic store, independently of the directives. While it's
+    /// off, records are dropped before any other work is done.
+    pub fn enabled_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.enabled_flag = Some(flag);
+        self
+    }
+
+    /// Drop all records after the first error or critical record passed
+    ///
+    /// For command line tools that should report a single error cleanly:
+    /// that error is logged, anything after it is not. Records logged
+    /// concurrently with the error may still get through.
+    pub fn stop_after_first_error(mut self) -> Self {
+        self.stop_after_first_error = true;
+        self
+    }
+
+    /// Log at most `per_second` records per second, across all modules
+    ///
+    /// Protects log pipelines from floods, whichever module causes them.
+    /// The limit is a token bucket holding `per_second` records, refilled
+    /// continuously, so bursts never exceed it, even at the turn of a
+    /// second. Records passing filtering and sampling beyond the limit are
+    /// dropped, and the first record logged after that is preceded by a
+    /// warning like `dropped 42 records over the global rate limit`,
+    /// regardless of the directives. `0` means no limit.
+    pub fn global_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limit = if per_second > 0 { Some(per_second) } else { None };
+        self
+    }
+
+    /// Thin out repeated messages with exponential backoff
+    ///
+    /// Of records with the same module and message, only the 1st, 2nd, 4th,
+    /// 8th and so on occurrence is logged, with a `skipped` key-value counting
+    /// the occurrences dropped since the previous one. Messages are told
+    /// apart by a hash, of which the `BACKOFF_CAPACITY` most recently seen are
+    /// kept, so a storm is cut down to a logarithmic trickle while rare
+    /// messages are logged as they are.
+    pub fn backoff_dedup(mut self) -> Self {
+        self.backoff_dedup = true;
+        self
+    }
+
+    /// Add the directive that enabled a record as `matched_directive`
+    ///
+    /// Records passed on get a `matched_directive` key-value with the module
+    /// directive deciding their level, rendered like `crate1::db=debug`, to
+    /// find out which rule let them through. Records enabled otherwise, eg.
+    /// by a key-value directive or the floor, don't get one.
+    pub fn annotate_match(mut self) -> Self {
+        self.annotate_match = true;
+        self
+    }
+
+    /// Keep the last `capacity` logged records in memory
+    ///
+    /// Records that pass all filtering are formatted as `LEVEL module: msg`
+    /// into a ring buffer, in addition to being logged, so they can be
+    /// retrieved with `EnvLogger::recent_logs`, eg. for a debug endpoint.
+    /// `0`, the default, disables the buffer.
+    pub fn memory_buffer(mut self, capacity: usize) -> Self {
+        self.memory_capacity = capacity;
+        self
+    }
+
+    /// Summarize the records that weren't logged, every `interval`
+    ///
+    /// Records dropped by the directives, the filter, sampling or the rate
+    /// limit are counted per module. Once `interval` has passed, the next record logged
+    /// through the `EnvLogger` first emits an info record like
+    /// `suppressed records since last summary: mod1=3, mod2=1` to the wrapped
+    /// drain, regardless of the directives. No thread is spawned; checking
+    /// whether a summary is due is an atomic load, but counting takes a lock
+    /// for every suppressed record.
+    pub fn suppression_summary(mut self, interval: Duration) -> Self {
+        self.summary_interval = Some(interval);
+        self
+    }
+
+    /// Always enable records at `level` or above, whatever the directives
+    ///
+    /// The floor beats every directive, including `off`, key-value
+    /// directives and elevations, so eg. errors are logged even with
+    /// `RUST_LOG=off`. The filter still applies. Can also be set with the
+    /// `RUST_LOG_MIN` environment variable, see `Env`.
+    pub fn min_floor(mut self, level: Level) -> Self {
+        self.min_floor = Some(level);
+        self
+    }
+
+    /// Suppress records from modules nested deeper than `n`
+    ///
+    /// The depth is the number of `::` separated segments, so with `2`,
+    /// `crate1::mod1` is logged, but not `crate1::mod1::generated`. The cap
+    /// only applies to global directives and to levels taken from the
+    /// record, like key-value directives: a directive naming a module
+    /// enables its submodules at any depth.
+    pub fn max_depth(mut self, n: usize) -> Self {
+        self.max_depth = Some(n);
+        self
+    }
+
+    /// Let records override the directives with a key-value
+    ///
+    /// If a record, or the logger it's logged through, carries `key` with a
+    /// level as value (eg. `log_level => "trace"`), that level is used
+    /// instead of the directives. This allows eg. raising the level for a
+    /// single request by adding the key to its `slog_scope` logger.
+    pub fn level_override_key(mut self, key: &'static str) -> Self {
+        self.level_override_key = Some(key);
+        self
+    }
+
+    /// Compile filters on first use instead of when building
+    ///
+    /// Saves compiling a regex at startup that may never be needed, eg. if
+    /// few records are enabled. Filters are only compiled by `build`, so
+    /// this applies to all of them, including ones parsed before the call,
+    /// eg. by `from_env`. As the filter isn't checked upfront, an invalid
+    /// one is only reported, like other warnings, once it's used, and then
+    /// matches everything. `validate_spec` can check it eagerly.
+    pub fn lazy_filter(mut self) -> Self {
+        self.lazy_filter = true;
+        self
+    }
+
+    /// Strip `#` comments from specs passed to `parse`
+    ///
+    /// A comment runs to the end of the line, eg. `info # default level`.
+    /// Specs may also span several lines, which are joined with `,`. Without
+    /// this a `#` is taken literally, eg. as part of the filter.
+    pub fn allow_comments(mut self) -> Self {
+        self.allow_comments = true;
+        self
+    }
+
+    /// Adds filters to the logger
+    ///
+    /// The given module (if any) will log at most the specified level provided.
+    /// If no module is provided then the filter will apply to all log messages.
+    pub fn filter(mut self,
+                  module: Option<&str>,
+                  level: FilterLevel) -> Self {
+        self.directives.push(LogDirective {
+            name: module.map(|s| s.to_string()),
+            level: level,
+            levels: None,
+            matcher: None,
+            priority: 0,
+        });
+        self
+    }
+
+    /// Adds a filter of priority `priority` to the logger
+    ///
+    /// Like `filter`, except that among the directives matching a module,
+    /// only those of the highest priority are considered, so this one can
+    /// win over a longer matching one. Can also be given in the spec with a
+    /// `!` per priority level, eg. `!app=warn`.
+    pub fn filter_priority(self,
+                           module: Option<&str>,
+                           level: FilterLevel,
+                           priority: u8) -> Self {
+        let mut builder = self.filter(module, level);
+        if let Some(directive) = builder.directives.last_mut() {
+            directive.priority = priority;
+        }
+        builder
+    }
+
+    /// Enables `level` for `module` (or all modules), for `count` records
+    ///
+    /// For capturing a burst of records, eg. the next 100 debug records of a
+    /// module, without flooding the output. Until they're used up, such
+    /// directives take precedence over the others, like key-value
+    /// directives. Only records they enable that the other directives
+    /// wouldn't count, so do those dropped later by the filter. Once used
+    /// up, a directive is inert, and the next best one applies as if it
+    /// never existed.
+    pub fn enable_for_count(mut self, module: Option<&str>, level: FilterLevel, count: u64) -> Self {
+        self.counted_directives.push((LogDirective::new(module, level), count));
+        self
+    }
+
+    /// Adds a filter for the modules `matcher` matches
+    ///
+    /// It takes precedence over module filters and directives, see
+    /// `LogDirective::with_matcher`.
+    pub fn filter_matcher<M: ModuleMatcher + 'static>(mut self, matcher: M, level: FilterLevel) -> Self {
+        self.directives.push(LogDirective::with_matcher(matcher, level));
+        self
+    }
+
+    /// Adds filters for several modules at once
+    ///
+    /// Same as calling `filter` for each pair in order.
+    pub fn filters(mut self, pairs: &[(Option<String>, FilterLevel)]) -> Self {
+        for &(ref module, level) in pairs {
+            self = self.filter(module.as_deref(), level);
+        }
+        self
+    }
+
+    /// Adds a filter by key-value
+    ///
+    /// Records carrying `key` with `value`, either themselves or through
+    /// their logger, will log at most the specified level, regardless of
+    /// the module directives.
+    pub fn filter_kv(mut self,
+                     key: &str,
+                     value: &str,
+                     level: FilterLevel) -> Self {
+        self.kv_directives.push(KvDirective {
+            key: key.to_string(),
+            value: value.to_string(),
+            level,
+        });
+        self
+    }
+
+    /// Adds a filter by thread name
+    ///
+    /// Records logged on a thread named `thread_name` will log at most the
+    /// specified level, regardless of the module directives, eg. to raise
+    /// the verbosity of one worker. Like a global directive, the level is
+    /// subject to the empty module policy, the allowlist, the floor and
+    /// `max_depth`. Key-value directives take precedence, and on such a
+    /// thread, `elevate_temporarily` and `enable_for_count` have no effect.
+    /// Unnamed threads only go by the module directives.
+    pub fn filter_thread(mut self, thread_name: &str, level: FilterLevel) -> Self {
+        self.thread_directives.push((thread_name.to_string(), level));
+        self
+    }
+
+    /// Parses the directives string in the same form as the RUST_LOG
+    /// environment variable.
+    ///
+    /// Directives accumulate over several calls, later ones taking
+    /// precedence for the same module. A filter replaces a previously
+    /// parsed one, while a spec without filter keeps it.
+    ///
+    /// See the module documentation for more details.
+    pub fn parse(self, filters: &str) -> Self {
+        let (builder, report) = self.parse_report(filters);
+        builder.warn(&report.rejected);
+        builder
+    }
+
+    /// Parses each of `specs`, like calling `parse` for each in order
+    ///
+    /// For assembling the configuration from several sources, eg. a file,
+    /// command line flags and the environment, without joining them first.
+    /// Later specs take precedence, for directives as for the filter.
+    pub fn parse_iter<I>(self, specs: I) -> Self
+    where I: IntoIterator, I::Item: AsRef<str> {
+        specs.into_iter().fold(self, |builder, spec| builder.parse(spec.as_ref()))
+    }
+
+    /// Parses the spec read from stdin, eg. `echo app=debug | myprog`
+    ///
+    /// See `parse_reader`.
+    pub fn parse_stdin(self) -> io::Result<Self> {
+        self.parse_reader(io::stdin().lock())
+    }
+
+    /// Parses the spec read from `reader`, to its end
+    ///
+    /// Surrounding whitespace, like a trailing newline, is trimmed, and if
+    /// nothing is left, the builder is returned unchanged. Input that isn't
+    /// valid UTF-8 is an error of kind `InvalidData`.
+    pub fn parse_reader<R: Read>(self, mut reader: R) -> io::Result<Self> {
+        let mut spec = String::new();
+        reader.read_to_string(&mut spec)?;
+        let spec = spec.trim();
+        if spec.is_empty() {
+            return Ok(self);
+        }
+        Ok(self.parse(spec))
+    }
+
+    /// Collect warnings about invalid specs instead of printing them
+    ///
+    /// Warnings of later calls to `parse` and friends are pushed, as human
+    /// readable messages, into the returned vector, eg. for showing them in
+    /// a UI.
+    pub fn collect_warnings(mut self) -> (Self, Arc<Mutex<Vec<String>>>) {
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        self.warnings = Some(warnings.clone());
+        (self, warnings)
+    }
+
+    fn warn(&self, errors: &[ParseError]) {
+        match self.warnings {
+            Some(ref warnings) => {
+                let mut warnings = warnings.lock().unwrap_or_else(|e| e.into_inner());
+                warnings.extend(errors.iter().map(|e| e.to_string()));
+            },
+            None => print_warnings(errors),
+        }
+    }
+
+    fn warn_message(&self, message: String) {
+        report_warning(self.warnings.as_deref(), message);
+    }
+
+    /// Register `spec` as the profile `name`
+    ///
+    /// A `@profile:name` directive in specs parsed later stands for `spec`,
+    /// so operators can pick curated configs by name, eg.
+    /// `RUST_LOG=@profile:debugging`. Profiles are applied before the rest of
+    /// the spec, which overrides them, and can't refer to other profiles.
+    /// Unknown profiles are reported like invalid directives.
+    pub fn profile(mut self, name: &str, spec: &str) -> Self {
+        self.profiles.insert(name.to_string(), spec.to_string());
+        self
+    }
+
+    /// Like `parse`, but instead of printing warnings, report which
+    /// directives were applied and which parts were ignored.
+    ///
+    /// Filters are only compiled by `build`, which reports invalid ones like
+    /// `parse` does. `validate_spec` can check them upfront.
+    pub fn parse_report(mut self, filters: &str) -> (Self, ParseReport) {
+        let filters = if self.allow_comments {
+            strip_comments(filters)
+        } else {
+            filters.into()
+        };
+
+        let (profiles, filters) = parse::split_profiles(&filters);
+        let mut report = ParseReport::default();
+        for name in profiles {
+            match self.profiles.get(name).cloned() {
+                Some(spec) => {
+                    let (builder, profile_report) = self.apply_spec(&spec);
+                    self = builder;
+                    report.accepted.extend(profile_report.accepted);
+                    report.rejected.extend(profile_report.rejected);
+                },
+                None => report.rejected.push(ParseError::UnknownProfile(name.to_string())),
+            }
+        }
+        let (builder, spec_report) = self.apply_spec(&filters);
+        report.accepted.extend(spec_report.accepted);
+        report.rejected.extend(spec_report.rejected);
+        (builder, report)
+    }
+
+    /// Apply the directives, filter and options of `filters`
+    fn apply_spec(mut self, filters: &str) -> (Self, ParseReport) {
+        let ParsedSpec {
+            directives,
+            kv_directives,
+            relative_directives,
+            filter,
+            filter_bound,
+            len_filter,
+            key_filter,
+            kv_threshold,
+            level_filters,
+            errors_only,
+            options,
+            errors,
+        } = parse_spec(filters);
+
+        if options.filter_disable {
+            self.filter = None;
+            self.default_filter = None;
+            self.filter_bound = None;
+            self.len_filter = None;
+            self.key_filter = None;
+            self.kv_threshold = None;
+            self.errors_only = false;
+            self.level_filters.clear();
+        } else {
+            if filter.is_some() || len_filter.is_some() || key_filter.is_some() || kv_threshold.is_some() || errors_only {
+                self.filter = filter.map(|filter| filter::Filter::lazy(&filter));
+                self.filter_bound = filter_bound;
+                self.len_filter = len_filter;
+                self.key_filter = key_filter;
+                self.kv_threshold = kv_threshold;
+                self.errors_only = errors_only;
+            }
+            for (level, pattern) in level_filters {
+                self.level_filters.push((level, pattern.map(|pattern| filter::Filter::lazy(&pattern))));
+            }
+        }
+        if let Some(n) = options.sample {
+            self = self.sample(n);
+        }
+
+        let report = ParseReport {
+            accepted: directives.clone(),
+            rejected: errors,
+        };
+        self.directives.extend(directives);
+        self.kv_directives.extend(kv_directives);
+        self.relative_directives.extend(relative_directives);
+        (self, report)
+    }
+
+    /// Parses directives and a filter from a TOML table
+    ///
+    /// The table, eg. the `[logging]` section of a config file, can have a
+    /// global `level`, a `modules` table of per-module levels, and a
+    /// `filter`, all as strings:
+    ///
+    /// ```toml
+    /// [logging]
+    /// level = "info"
+    /// filter = "^request"
+    ///
+    /// [logging.modules]
+    /// "hyper" = "warn"
+    /// "myapp::db" = "debug"
+    /// ```
+    ///
+    /// Module names have to be paths like `myapp::db`, and the filter is
+    /// taken as is, without the `@` suffixes or `;` options of a spec.
+    /// Unlike `parse`, nothing is applied if any part is invalid, and the
+    /// first problem is returned.
+    #[cfg(feature = "toml")]
+    pub fn parse_toml(mut self, table: &toml::value::Table) -> result::Result<Self, ParseError> {
+        let mut directives = Vec::new();
+        let mut filter = None;
+        for (key, value) in table {
+            match (key.as_str(), value) {
+                ("level", &toml::Value::String(ref level)) => directives.push(toml_directive(None, level)?),
+                ("modules", &toml::Value::Table(ref modules)) => {
+                    for (module, level) in modules {
+                        if !is_module_path(module) {
+                            return Err(ParseError::InvalidDirective(module.clone()));
+                        }
+                        match level.as_str() {
+                            Some(level) => directives.push(toml_directive(Some(module), level)?),
+                            None => return Err(ParseError::InvalidDirective(format!("{}={}", module, level))),
+                        }
+                    }
+                },
+                ("filter", &toml::Value::String(ref pattern)) => filter = Some(pattern.clone()),
+                _ => return Err(ParseError::InvalidOption(format!("{}={}", key, value))),
+            }
+        }
+
+        if filter.is_some() {
+            // Checked eagerly, even if the filter is compiled lazily
+            let mut errors = Vec::new();
+            compile_filter(filter.clone(), &mut errors);
+            if !errors.is_empty() {
+                return Err(errors.remove(0));
+            }
+            self.filter = filter.map(|filter| filter::Filter::lazy(&filter));
+            self.filter_bound = None;
+            self.len_filter = None;
+            self.key_filter = None;
+            self.kv_threshold = None;
+            self.errors_only = false;
+        }
+        self.directives.extend(directives);
+        Ok(self)
+    }
+
+    /// Parses the value of the environment variable `var`, if it's set
+    pub fn parse_env(self, var: &str) -> Self {
+        match env::var(var) {
+            Ok(s) => self.parse(&s),
+            Err(_) => self,
+        }
+    }
+
+    /// Parses the values of several environment variables
+    ///
+    /// The variables are parsed in order, skipping unset and empty ones, so
+    /// directives from later variables override earlier ones for the same
+    /// module, eg. `&["RUST_LOG_BASE", "RUST_LOG", "RUST_LOG_EXTRA"]`.
+    pub fn parse_envs(mut self, vars: &[&str]) -> Self {
+        for var in vars {
+            match env::var(var) {
+                Ok(ref s) if !s.is_empty() => self = self.parse(s),
+                _ => {},
+            }
+        }
+        self
+    }
+
+    /// Build an env logger, whose directives can be replaced through the
+    /// returned sender
+    ///
+    /// Sending new `Directives` makes the logger apply them to the next
+    /// record, eg. from an async task watching a config file. They're
+    /// matched by longest prefix, regardless of `match_mode`, and aren't
+    /// reflected by `filter()` and `min_level()`. A `control_socket` would
+    /// replace them too, so it's ignored, with a warning.
+    #[cfg(feature = "tokio")]
+    pub fn build_with_watch(self) -> (EnvLogger<T>, watch::Sender<Directives>) {
+        #[cfg(all(unix, feature = "control-socket"))]
+        let builder = self.without_control_socket();
+        #[cfg(not(all(unix, feature = "control-socket")))]
+        let builder = self;
+        let mut logger = builder.build();
+        let (sender, receiver) = watch::channel(Directives::new(logger.directives.clone()));
+        logger.watch = Some(AssertUnwindSafe(receiver));
+        (logger, sender)
+    }
+
+    /// Take new directives from the Unix socket at `path` while the logger
+    /// is alive
+    ///
+    /// Building spawns a thread listening on `path`, eg. for
+    /// `echo 'info,app::db=debug' | socat - UNIX-CONNECT:/run/app/log.sock`.
+    /// Each connection sends a spec on one line and gets back `ok` once its
+    /// module directives replace the active ones, like with
+    /// `build_with_watch`, or one `error: ` line per problem, in which case
+    /// nothing is applied. Filters, key-value directives and options can't
+    /// be changed this way, and are reported as problems.
+    ///
+    /// A socket left at `path` by a previous run is replaced; if the socket
+    /// can't be set up, the logger keeps its directives, with a warning
+    /// like for invalid specs. Dropping the logger stops the thread and removes the socket.
+    /// Not supported by `build_with_watch`.
+    #[cfg(all(unix, feature = "control-socket"))]
+    pub fn control_socket(mut self, path: &Path) -> Self {
+        self.control_socket = Some(path.to_path_buf());
+        self
+    }
+
+    /// Forget the control socket, whose directives `build_with_watch` would
+    /// replace
+    #[cfg(all(unix, feature = "control-socket"))]
+    fn without_control_socket(mut self) -> Self {
+        if let Some(path) = self.control_socket.take() {
+            self.warn_message(format!("ignoring the control socket {}, directives are replaced by build_with_watch",
+                                      path.display()));
+        }
+        self
+    }
+
+    /// Don't enable errors when no directive was given
+    ///
+    /// By default, building without any directive logs errors from all
+    /// modules. With this, such a logger starts off, logging nothing until
+    /// configured, and relative directives adjust `off` instead of `error`.
+    pub fn no_default(mut self) -> Self {
+        self.no_default = true;
+        self
+    }
+
+    /// Build an env logger.
+    pub fn build(self) -> EnvLogger<T> {
+        self.build_with_buf()
+    }
+
+    /// Build an env logger formatting messages into buffers of type `B`
+    /// for filtering
+    ///
+    /// For services where the allocations of the default `String` buffers
+    /// matter, eg. with a `MessageBuf` drawing from a pool. Messages are
+    /// still passed on as they were logged.
+    pub fn build_with_buf<B: MessageBuf>(mut self) -> EnvLogger<T, B> {
+        let default_level = if self.no_default { FilterLevel::Off } else { FilterLevel::Error };
+        if self.directives.is_empty() {
+            // Adds the default filter if none exist
+            self.directives.push(LogDirective {
+                name: None,
+                level: default_level,
+                levels: None,
+                matcher: None,
+                priority: 0,
+            });
+        }
+
+        // Relative directives adjust the last global level
+        parse::resolve_relative(&mut self.directives, &self.relative_directives, default_level);
+
+        sort_directives(&mut self.directives, self.match_mode);
+        // Filters are only compiled now, so `lazy_filter` applies to all of
+        // them, whenever it was called
+        if !self.lazy_filter {
+            let mut errors = Vec::new();
+            self.filter = compile_now(self.filter.take(), &mut errors);
+            self.default_filter = compile_now(self.default_filter.take(), &mut errors);
+            for (level, filter) in mem::take(&mut self.level_filters) {
+                let invalid = errors.len();
+                let filter = compile_now(filter, &mut errors);
+                if errors.len() == invalid {
+                    self.level_filters.push((level, filter));
+                }
+            }
+            self.warn(&errors);
+        } else {
+            let level_filters = self.level_filters.iter_mut().filter_map(|filter| filter.1.as_mut());
+            for filter in self.filter.iter_mut().chain(self.default_filter.iter_mut()).chain(level_filters) {
+                filter.report_to(self.warnings.clone());
+            }
+        }
+        // Records more verbose than slog's compile-time max level are never
+        // created, so directives enabling them have no effect
+        if cfg!(debug_assertions) {
+            let static_max = static_max_level();
+            if let Some(level) = self.directives.iter().map(|d| d.level).max().filter(|&level| level > static_max) {
+                self.warn_message(format!("logging spec enables level {}, but slog is compiled with max level {}",
+                                          level, static_max));
+            }
+        }
+        self.routes.sort_by_key(|route| route.0.len());
+        self.formats.sort_by_key(|format| format.0.len());
+        self.counted_directives.sort_by_key(|d| d.0.name.as_ref().map(|name| name.len()));
+
+        let LogBuilder {
+            drain,
+            directives,
+            kv_directives,
+            thread_directives,
+            relative_directives: _,
+            no_default: _,
+            counted_directives,
+            filter,
+            default_filter,
+            filter_bound,
+            len_filter,
+            key_filter,
+            kv_threshold,
+            level_filters,
+            errors_only,
+            case_insensitive,
+            boundary_aware,
+            strip_ansi,
+            prefer_quieter,
+            allowlist,
+            match_mode,
+            empty_module_policy,
+            filter_target,
+            max_filter_len,
+            oversized_policy,
+            required_prefix,
+            sample,
+            rate_limit,
+            backoff_dedup,
+            annotate_match,
+            enabled_flag,
+            stop_after_first_error,
+            min_floor,
+            max_depth,
+            level_override_key,
+            allow_comments,
+            profiles,
+            lazy_filter: _,
+            filter_miss_drain,
+            audit_drain,
+            map_message,
+            routes,
+            formats,
+            memory_capacity,
+            summary_interval,
+            warnings,
+            #[cfg(all(unix, feature = "control-socket"))]
+            control_socket,
+        } = self;
+
+        let logger = EnvLogger {
+            drain,
+            directives,
+            kv_directives,
+            thread_directives,
+            counted_directives: counted_directives.into_iter()
+                .map(|(directive, count)| (directive, AtomicU64::new(count)))
+                .collect(),
+            filter,
+            default_filter,
+            filter_bound,
+            len_filter,
+            key_filter,
+            kv_threshold,
+            level_filters,
+            errors_only,
+            case_insensitive,
+            boundary_aware,
+            strip_ansi,
+            prefer_quieter,
+            allowlist,
+            match_mode,
+            empty_module_policy,
+            filter_target,
+            max_filter_len,
+            oversized_policy,
+            required_prefix,
+            sample,
+            rate_limit,
+            enabled_flag,
+            stopped: stop_after_first_error.then(|| AtomicBool::new(false)),
+            min_floor,
+            max_depth,
+            allow_comments,
+            profiles,
+            warnings,
+            sampled: AtomicUsize::new(0),
+            rate_full_at: AtomicU64::new(0),
+            rate_dropped: AtomicUsize::new(0),
+            backoff: backoff_dedup.then(|| Mutex::new(VecDeque::with_capacity(BACKOFF_CAPACITY))),
+            annotate_match,
+            filter_miss_drain,
+            audit_drain,
+            map_message,
+            routes,
+            formats,
+            memory_capacity,
+            memory: Mutex::new(VecDeque::with_capacity(memory_capacity)),
+            summary_interval,
+            summary_due: AtomicU64::new(summary_interval.map_or(0, nanos)),
+            created: Instant::now(),
+            suppressed: Mutex::new(HashMap::new()),
+            metrics: MetricCounters::default(),
+            elevated: AtomicBool::new(false),
+            elevation: Mutex::new(None),
+            shadowed: AtomicBool::new(false),
+            shadow: Mutex::new(None),
+            shadow_diffs: AtomicU64::new(0),
+            #[cfg(feature = "stats")]
+            stats: Mutex::new(HashMap::new()),
+            #[cfg(feature = "globset")]
+            globs: globs::GlobIndex::new(&directives),
+            #[cfg(feature = "tokio")]
+            watch: None,
+            #[cfg(all(unix, feature = "control-socket"))]
+            control: None,
+            level_override_key,
+            _buf: PhantomData,
+        };
+
+        #[cfg(all(unix, feature = "control-socket"))]
+        let logger = {
+            let mut logger = logger;
+            if let Some(path) = control_socket {
+                if let Err(e) = control::attach(&mut logger, &path) {
+                    report_warning(logger.warnings.as_deref(),
+                                   format!("can't listen for directives on {}: {}", path.display(), e));
+                }
+            }
+            logger
+        };
+
+        logger
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T : Drain<Ok = (), Err = Never> + 'static> LogBuilder<T> {
+    /// Build the logger and install it for `ThreadLocalDrain` on the current
+    /// thread
+    ///
+    /// Records logged through a `ThreadLocalDrain` on this thread are passed
+    /// to this logger until the returned guard is dropped, while other
+    /// threads use the loggers they installed. This gives eg. tests running
+    /// in parallel their own config, without a process-wide logger.
+    ///
+    /// ```
+    /// # extern crate slog;
+    /// # extern crate slog_envlogger;
+    /// use slog_envlogger::{LogBuilder, ThreadLocalDrain};
+    ///
+    /// # fn main() {
+    /// let log = slog::Logger::root(ThreadLocalDrain, slog::o!());
+    /// let _guard = LogBuilder::new(slog::Discard).parse("debug").install_thread_local();
+    /// slog::debug!(log, "passed to the installed logger");
+    /// # }
+    /// ```
+    pub fn install_thread_local(self) -> ThreadGuard {
+        let logger: ThreadLogger = Arc::new(self.build());
+        let id = NEXT_GUARD.fetch_add(1, Ordering::Relaxed);
+        THREAD_LOGGERS.with(|loggers| loggers.borrow_mut().push((id, logger)));
+        ThreadGuard { id, _thread: PhantomData }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T : Drain + Clone, B : MessageBuf> EnvLogger<T, B> {
+    /// A new logger over a clone of the drain, with the directives of
+    /// `extra_spec` layered on top of this one's
+    ///
+    /// For giving a subsystem a tighter or looser config than the global one.
+    /// The fork starts with this logger's configured directives, filters and
+    /// options, then parses `extra_spec` like `LogBuilder::parse`: its
+    /// directives win over equally specific ones of this logger, and its
+    /// filter, if any, replaces this one's.
+    ///
+    /// The directives are the ones in effect, including those received
+    /// through `build_with_watch` or a control socket, though the fork
+    /// doesn't receive later ones. Profiles, `allow_comments` and the
+    /// default filter carry over, and warnings about `extra_spec` go to the
+    /// collector of `collect_warnings`, if this logger has one.
+    ///
+    /// Routes, the `filter_miss_drain`, the `audit_drain` and `map_message`
+    /// can't be cloned, so the fork has none of them, and its counters start
+    /// at zero. The two loggers are independent from then on, except that
+    /// they share the `enabled_flag` and the warnings collector.
+    pub fn fork(&self, extra_spec: &str) -> EnvLogger<T, B> {
+        let copy_filter = |filter: &Option<filter::Filter>| {
+            filter.as_ref().map(|filter| filter::Filter::lazy(filter.source()))
+        };
+        let mut builder = LogBuilder::new(self.drain.clone());
+        builder.directives = self.active_directives();
+        builder.kv_directives = self.kv_directives.clone();
+        builder.thread_directives = self.thread_directives.clone();
+        builder.counted_directives = self.counted_directives.iter()
+            .map(|(directive, remaining)| (directive.clone(), remaining.load(Ordering::Relaxed)))
+            .collect();
+        builder.filter = copy_filter(&self.filter);
+        builder.default_filter = copy_filter(&self.default_filter);
+        builder.filter_bound = self.filter_bound;
+        builder.len_filter = self.len_filter;
+        builder.key_filter = self.key_filter.clone();
+        builder.kv_threshold = self.kv_threshold.clone();
+        builder.level_filters = self.level_filters.iter()
+            .map(|(level, filter)| (*level, copy_filter(filter)))
+            .collect();
+        builder.errors_only = self.errors_only;
+        builder.case_insensitive = self.case_insensitive;
+        builder.boundary_aware = self.boundary_aware;
+        builder.strip_ansi = self.strip_ansi;
+        builder.prefer_quieter = self.prefer_quieter;
+        builder.allowlist = self.allowlist.clone();
+        builder.match_mode = self.match_mode;
+        builder.empty_module_policy = self.empty_module_policy;
+        builder.filter_target = self.filter_target;
+        builder.max_filter_len = self.max_filter_len;
+        builder.oversized_policy = self.oversized_policy;
+        builder.required_prefix = self.required_prefix.clone();
+        builder.sample = self.sample;
+        builder.rate_limit = self.rate_limit;
+        builder.backoff_dedup = self.backoff.is_some();
+        builder.annotate_match = self.annotate_match;
+        builder.enabled_flag = self.enabled_flag.clone();
+        builder.stop_after_first_error = self.stopped.is_some();
+        builder.min_floor = self.min_floor;
+        builder.max_depth = self.max_depth;
+        builder.level_override_key = self.level_override_key;
+        builder.allow_comments = self.allow_comments;
+        builder.profiles = self.profiles.clone();
+        builder.warnings = self.warnings.clone();
+        builder.formats = self.formats.clone();
+        builder.memory_capacity = self.memory_capacity;
+        builder.summary_interval = self.summary_interval;
+        builder.parse(extra_spec).build_with_buf()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T : Drain> EnvLogger<T> {
+    /// Create an `EnvLogger` using the `RUST_LOG` environment variable
+    ///
+    /// Same as the crate's `new`.
+    pub fn new(d : T) -> Self {
+        EnvLogger::from_env(d, Env::default())
+    }
+
+    /// Create an `EnvLogger` using the `RUST_LOG` environment variable
+    ///
+    /// Same as `new`, named after `env_logger`'s API.
+    pub fn from_default_env(d : T) -> Self {
+        EnvLogger::new(d)
+    }
+
+    /// Create an `EnvLogger` using the spec from `env`
+    ///
+    /// If `RUST_LOG_DEBUG` (see `Env::debug`) is set, the effective
+    /// configuration is printed to stderr.
+    pub fn from_env<E: Into<Env>>(d : T, env: E) -> Self {
+        let env = env.into();
+        let logger = LogBuilder::from_env(d, env.clone()).build();
+        logger.print_config(&env);
+        logger
+    }
+
+    /// Like `from_env`, but collecting warnings about the spec instead of
+    /// printing them, see `LogBuilder::collect_warnings`
+    #[cfg(feature = "init")]
+    pub(crate) fn from_env_collecting(d: T, env: Env) -> (Self, Arc<Mutex<Vec<String>>>) {
+        let (builder, warnings) = LogBuilder::new(d).collect_warnings();
+        let logger = builder.apply_env(&env).build();
+        logger.print_config(&env);
+        (logger, warnings)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T : Drain, B : MessageBuf> EnvLogger<T, B> {
+    fn print_config(&self, env: &Env) {
+        if let Some(dump) = self.config_dump(env) {
+            eprint!("{}", dump);
+        }
+    }
+
+    /// The effective configuration, if `env` asks for it
+    ///
+    /// Lists the rendered spec, then each directive with where it came from.
+    fn config_dump(&self, env: &Env) -> Option<String> {
+        if !env.get_debug() {
+            return None;
+        }
+
+        let spec = env.get_filter();
+        let parsed = spec.as_deref().map(parse_spec).unwrap_or_default();
+        let source = |from_spec: bool| match (from_spec, env::var(&env.var)) {
+            (true, Ok(_)) => env.var.as_str(),
+            (true, Err(_)) => "default_filter_or",
+            (false, _) => "built-in default",
+        };
+
+        let mut dump = format!("logging config: {}\n",
+                               directives_to_spec(&self.directives, self.filter_source()));
+        for directive in &self.directives {
+            let from_spec = parsed.directives.contains(directive) ||
+                parsed.relative_directives.iter().any(|d| directive.name() == d.name());
+            let _ = writeln!(dump, "  {} (from {})",
+                             directives_to_spec(std::slice::from_ref(directive), None), source(from_spec));
+        }
+        if let Some(filter) = self.filter_source() {
+            let _ = writeln!(dump, "  filter {} (from {})", filter, source(parsed.filter.is_some()));
+        }
+        if let (Some(floor), Some(var)) = (self.min_floor, env.floor_var.as_ref()) {
+            let _ = writeln!(dump, "  floor {} (from {})", floor.as_str(), var);
+        }
+        Some(dump)
+    }
+
+    /// Number of records logged per module
+    ///
+    /// Only records that passed all filtering are counted.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> HashMap<String, u64> {
+        let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        stats.iter().map(|(module, count)| (module.to_string(), *count)).collect()
+    }
+
+    /// Counts of records passed and dropped, since creation or the last
+    /// `reset_counters`
+    ///
+    /// Unlike `stats`, always available, as counting is just a few atomic
+    /// increments. Records dropped for other reasons, like sampling, are
+    /// counted neither as passed nor as dropped.
+    pub fn metrics(&self) -> Metrics {
+        Metrics {
+            passed: self.metrics.passed.load(Ordering::Relaxed),
+            dropped_level: self.metrics.dropped_level.load(Ordering::Relaxed),
+            dropped_filter: self.metrics.dropped_filter.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Zero all counters, eg. after scraping them for metrics
+    ///
+    /// Clears the `stats` (with the `stats` feature), the `metrics`, the
+    /// records counted for the next `suppression_summary`, and the sampling
+    /// counter, so the next record passing filtering is logged again, as do
+    /// the messages seen by `backoff_dedup`.
+    pub fn reset_counters(&self) {
+        #[cfg(feature = "stats")]
+        self.stats.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.metrics.passed.store(0, Ordering::Relaxed);
+        self.metrics.dropped_level.store(0, Ordering::Relaxed);
+        self.metrics.dropped_filter.store(0, Ordering::Relaxed);
+        self.suppressed.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.sampled.store(0, Ordering::Relaxed);
+        if let Some(ref backoff) = self.backoff {
+            backoff.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+
+    /// Reference to the wrapped drain
+    ///
+    /// Anything done through it has to respect the drain's own thread-safety,
+    /// as `EnvLogger` may concurrently be logging to it.
+    pub fn drain(&self) -> &T {
+        &self.drain
+    }
+
+    /// Unwrap the wrapped drain
+    pub fn into_inner(self) -> T {
+        self.drain
+    }
+
+    pub fn filter(&self) -> FilterLevel {
+        let floor = self.min_floor
+            .and_then(|floor| FilterLevel::from_usize(floor.as_usize()))
+            .unwrap_or(FilterLevel::Off);
+        self.directives.iter()
+            .map(|d| d.level).max()
+            .unwrap_or(FilterLevel::Off)
+            .max(floor)
+    }
+
+    /// Least verbose level enabled by any directive
+    ///
+    /// Like with `filter`, the floor raises it.
+    pub fn min_level(&self) -> FilterLevel {
+        let floor = self.min_floor
+            .and_then(|floor| FilterLevel::from_usize(floor.as_usize()))
+            .unwrap_or(FilterLevel::Off);
+        self.directives.iter()
+            .map(|d| d.level).min()
+            .unwrap_or(FilterLevel::Off)
+            .max(floor)
+    }
+
+    /// Assert, in debug builds, that no level above slog's compile-time max
+    /// level is enabled
+    ///
+    /// Records more verbose than the compile-time max level are never
+    /// created, so directives enabling them have no effect. `build` warns
+    /// about such directives in debug builds; this makes them panic instead,
+    /// for programs whose spec is fixed, eg. in tests.
+    ///
+    /// As `EnvLogger` already filters by level, wrapping it in a
+    /// `slog::LevelFilter` set to the compile-time max level is redundant.
+    pub fn with_compiletime_floor(self) -> Self {
+        debug_assert!(self.filter() <= static_max_level(),
+                      "logging spec enables level {}, but slog is compiled with max level {}",
+                      self.filter(), static_max_level());
+        self
+    }
+
+    /// Level of the global directive deciding for modules without their own
+    fn global_level(&self) -> FilterLevel {
+        self.directives.iter().rev()
+            .find(|d| d.name.is_none() && d.matcher.is_none())
+            .map_or(FilterLevel::Error, |d| d.level)
+    }
+
+    /// The filter pattern, exactly as given in the spec or to the builder
+    ///
+    /// Suitable for rendering the spec back, eg. with `directives_to_spec`.
+    pub fn filter_source(&self) -> Option<&str> {
+        self.general_filter().map(|filter| filter.source())
+    }
+
+    /// Whether the filter matches `sample`, or `None` if there's no filter
+    ///
+    /// For previewing a filter on example messages, eg. in a config UI. The
+    /// sample is checked as given, like a message with
+    /// `FilterTarget::Message`, after stripping escape sequences if
+    /// `strip_ansi_for_filter` is set.
+    pub fn filter_matches(&self, sample: &str) -> Option<bool> {
+        let filter = self.general_filter()?;
+        if self.strip_ansi && sample.contains('\x1b') {
+            let mut sample = sample.to_string();
+            strip_ansi(&mut sample);
+            return Some(filter.is_match(&sample));
+        }
+        Some(filter.is_match(sample))
+    }
+
+    /// The records kept by `LogBuilder::memory_buffer`, oldest first
+    pub fn recent_logs(&self) -> Vec<String> {
+        let memory = self.memory.lock().unwrap_or_else(|e| e.into_inner());
+        memory.iter().cloned().collect()
+    }
+
+    fn remember(&self, info: &Record) {
+        let line = with_msg::<B, _, _>(info, |msg| {
+            format!("{} {}: {}", info.level().as_short_str(), info.module(), msg)
+        });
+        let mut memory = self.memory.lock().unwrap_or_else(|e| e.into_inner());
+        if memory.len() == self.memory_capacity {
+            memory.pop_front();
+        }
+        memory.push_back(line);
+    }
+
+    /// Temporarily apply the directives of `spec` on top of the configured ones
+    ///
+    /// For modules matched by the extra directives, these take precedence
+    /// until `duration` has passed. Relative directives adjust the global
+    /// level of `spec`, or else the configured one. Any filter in `spec` is
+    /// ignored. The
+    /// expiry is checked lazily when logging, so no thread is spawned, and
+    /// there's no cost besides an atomic load while no elevation is active.
+    /// A new elevation replaces a still active one.
+    pub fn elevate_temporarily(&self, spec: &str, duration: Duration) {
+        let parsed = parse_spec(spec);
+        self.log_warnings(&parsed.errors);
+        let mut directives = parsed.resolved_directives(self.global_level());
+        sort_directives(&mut directives, self.match_mode);
+
+        let mut elevation = self.elevation.lock().unwrap_or_else(|e| e.into_inner());
+        *elevation = Some(Elevation {
+            directives,
+            until: Instant::now() + duration,
+        });
+        self.elevated.store(true, Ordering::Release);
+    }
+
+    /// Evaluate the directives of `spec` alongside the active ones
+    ///
+    /// For rolling out a new config safely: every record is also checked
+    /// against `spec`, and `shadow_diff_count` counts the records it would
+    /// have decided differently, while the output stays the same. Only the
+    /// module directives of `spec` are compared; a filter in it is ignored.
+    /// A new shadow config replaces the previous one and resets the count,
+    /// and an empty `spec` stops the comparison. While a shadow config is
+    /// set, every record takes a lock.
+    pub fn set_shadow_config(&self, spec: &str) {
+        let mut shadow = self.shadow.lock().unwrap_or_else(|e| e.into_inner());
+        self.shadow_diffs.store(0, Ordering::Relaxed);
+        if spec.trim().is_empty() {
+            *shadow = None;
+            self.shadowed.store(false, Ordering::Release);
+            return;
+        }
+
+        let parsed = parse_spec(spec);
+        self.log_warnings(&parsed.errors);
+        let mut directives = parsed.resolved_directives(FilterLevel::Error);
+        if directives.is_empty() {
+            directives.push(LogDirective::new(None, FilterLevel::Error));
+        }
+        sort_directives(&mut directives, self.match_mode);
+        *shadow = Some(directives);
+        self.shadowed.store(true, Ordering::Release);
+    }
+
+    /// Number of records the shadow config would have decided differently,
+    /// see `set_shadow_config`
+    pub fn shadow_diff_count(&self) -> u64 {
+        self.shadow_diffs.load(Ordering::Relaxed)
+    }
+
+    /// Log `record` to the wrapped drain, bypassing all filtering
+    ///
+    /// For messages that must never be hidden by the logging spec, not even
+    /// by `off`. Use sparingly.
+    pub fn always_log(&self, record: &Record, kv: &OwnedKVList) -> result::Result<T::Ok, T::Err> {
+        self.log_raw(record, kv)
+    }
+
+    /// Run `f`, dropping all records logged on the current thread meanwhile
+    ///
+    /// A programmatic mute, eg. around calls into a noisy library, that
+    /// applies whatever the directives. It covers every `EnvLogger` on the
+    /// thread, not only this one, but not `always_log`. Calls can be nested,
+    /// and logging resumes when the outermost one returns or panics.
+    pub fn with_silenced<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        SILENCED.with(|silenced| silenced.set(silenced.get() + 1));
+        let _guard = SilenceGuard;
+        f()
+    }
+
+    fn log_raw(&self, record: &Record, kv: &OwnedKVList) -> result::Result<T::Ok, T::Err> {
+        self.drain.log(record, kv)
+    }
+
+    /// Report problems with a spec through the wrapped drain
+    ///
+    /// Goes around filtering, so a spec of `off` can't hide that another one
+    /// is broken.
+    fn log_warnings(&self, errors: &[ParseError]) {
+        static RS: RecordStatic<'static> = slog::record_static!(Level::Warning, "");
+        let kv = OwnedKVList::from(slog::o!());
+        for e in errors {
+            let _ = self.log_raw(&Record::new(&RS, &format_args!("{}", e), slog::b!()), &kv);
+        }
+    }
+
+    /// Occurrences of the record's message skipped by the backoff before it,
+    /// or `None` if it's skipped itself
+    fn backoff_skipped(&self, backoff: &Mutex<VecDeque<(u64, u64)>>, info: &Record) -> Option<u64> {
+        let hash = with_msg::<B, _, _>(info, |msg| {
+            let mut hasher = DefaultHasher::new();
+            info.module().hash(&mut hasher);
+            msg.hash(&mut hasher);
+            hasher.finish()
+        });
+
+        let mut seen = backoff.lock().unwrap_or_else(|e| e.into_inner());
+        let count = match seen.iter().position(|&(h, _)| h == hash) {
+            Some(i) => seen.remove(i).map_or(1, |(_, count)| count + 1),
+            None => 1,
+        };
+        seen.push_front((hash, count));
+        seen.truncate(BACKOFF_CAPACITY);
+        // Logged at powers of two, so half of the ones since the last are skipped
+        count.is_power_of_two().then(|| (count / 2).saturating_sub(1))
+    }
+
+    /// Count a record against the global rate limit, whether it's within it
+    ///
+    /// `now` is in nanoseconds since the logger was created.
+    fn within_rate_limit(&self, per_second: u32, now: u64) -> bool {
+        // Time for one token to come back, and for all of them
+        let refill = 1_000_000_000 / u64::from(per_second.max(1));
+        let capacity = refill * u64::from(per_second);
+        let mut full_at = self.rate_full_at.load(Ordering::Relaxed);
+        loop {
+            let next = full_at.max(now) + refill;
+            if next > now + capacity {
+                return false;
+            }
+            match self.rate_full_at.compare_exchange_weak(full_at, next, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(actual) => full_at = actual,
+            }
+        }
+    }
+
+    fn count_suppressed(&self, info: &Record) {
+        if self.summary_interval.is_some() {
+            let mut suppressed = self.suppressed.lock().unwrap_or_else(|e| e.into_inner());
+            *suppressed.entry(info.module()).or_insert(0) += 1;
+        }
+    }
+
+    /// Pass the trace of a record dropped by the directives to `d`, see
+    /// `LogBuilder::audit_drain`
+    fn audit(&self, d: &BoxedDrain<T::Err>, info: &Record) -> result::Result<(), T::Err> {
+        let rs = RecordStatic {
+            location: info.location(),
+            tag: info.tag(),
+            level: info.level(),
+        };
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        d.log(&Record::new(&rs, &format_args!(""), slog::b!("ts" => ts)), &OwnedKVList::from(slog::o!()))
+    }
+
+    /// Emit a summary of suppressed records, if the interval has passed
+    fn summarize_suppressed(&self) {
+        let interval = match self.summary_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+        let now = nanos(self.created.elapsed());
+        let due = self.summary_due.load(Ordering::Relaxed);
+        if now < due {
+            return;
+        }
+        // Only the thread moving the deadline on emits the summary
+        let next = now.saturating_add(nanos(interval));
+        if self.summary_due.compare_exchange(due, next, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+            return;
+        }
+        let counts = mem::take(&mut *self.suppressed.lock().unwrap_or_else(|e| e.into_inner()));
+        if counts.is_empty() {
+            return;
+        }
+
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort();
+        let summary = counts.iter()
+            .map(|&(module, n)| format!("{}={}", module, n))
+            .collect::<Vec<_>>()
+            .join(", ");
+        static RS: RecordStatic<'static> = slog::record_static!(Level::Info, "");
+        let msg = format_args!("suppressed records since last summary: {}", summary);
+        let _ = self.log_raw(&Record::new(&RS, &msg, slog::b!()), &OwnedKVList::from(slog::o!()));
+    }
+
+    /// Whether the directives enable records at `level` from `module`
+    ///
+    /// Only module directives are considered, not key-value directives,
+    /// elevations or the filter, as they depend on the record.
+    pub fn would_log(&self, level: Level, module: &str) -> bool {
+        self.enabled(level, module)
+    }
+
+    /// Why records at `level` from `module` are logged or not
+    ///
+    /// A richer `would_log`, telling which directive decided and whether a
+    /// filter applies on top of it, eg. for a debugging command. Like
+    /// `would_log`, it doesn't consider what depends on the record.
+    pub fn explain(&self, level: Level, module: &str) -> Explanation {
+        Explanation {
+            directive: self.matched_directive(module),
+            enabled: self.enabled(level, module),
+            filtered: self.filter_applies(level),
+        }
+    }
+
+    /// The directive deciding the level for `module`, with its position
+    fn matched_directive(&self, module: &str) -> Option<(usize, LogDirective)> {
+        let matched = |directives: &[LogDirective]| {
+            self.directive_for(directives, module).map(|directive| {
+                let index = directives.iter().position(|d| std::ptr::eq(d, directive))
+                    .expect("directive is one of `directives`");
+                (index, directive.clone())
+            })
+        };
+        #[cfg(feature = "tokio")]
+        {
+            if let Some(ref watch) = self.watch {
+                return matched(&watch.borrow().directives);
+            }
+        }
+        matched(&self.directives)
+    }
+
+    fn enabled(&self, level: Level, module: &str) -> bool {
+        #[cfg(feature = "tokio")]
+        {
+            if let Some(ref watch) = self.watch {
+                return self.enabled_by(&watch.borrow().directives, level, module);
+            }
+        }
+        self.enabled_by(&self.directives, level, module)
+    }
+
+    /// The directives in effect, the last ones received by the watch, if any
+    fn active_directives(&self) -> Vec<LogDirective> {
+        #[cfg(feature = "tokio")]
+        {
+            if let Some(ref watch) = self.watch {
+                return watch.borrow().directives.clone();
+            }
+        }
+        self.directives.clone()
+    }
+
+    /// Whether records at `level` from `module` are enabled, with `directives`
+    fn enabled_by(&self, directives: &[LogDirective], level: Level, module: &str) -> bool {
+        if let Some(enabled) = self.empty_module_decision(module) {
+            return enabled;
+        }
+        if !self.allowed(module) {
+            return false;
+        }
+        if self.above_floor(level) {
+            return true;
+        }
+        self.directives_enable(directives, level, module)
+    }
+
+    /// Whether `level`, overriding the directives, enables the record
+    ///
+    /// The empty module policy, the allowlist and the floor apply just like
+    /// with the directives.
+    fn override_enables(&self, level: FilterLevel, info: &Record) -> bool {
+        if let Some(enabled) = self.empty_module_decision(info.module()) {
+            return enabled;
+        }
+        self.allowed(info.module()) && (level.accepts(info.level()) || self.above_floor(info.level()))
+    }
+
+    /// What the empty module policy decides, if `module` is empty
+    fn empty_module_decision(&self, module: &str) -> Option<bool> {
+        if !module.is_empty() {
+            return None;
+        }
+        match self.empty_module_policy {
+            EmptyModulePolicy::TreatAsGlobal => None,
+            EmptyModulePolicy::AlwaysSuppress => Some(false),
+            EmptyModulePolicy::AlwaysLog => Some(true),
+        }
+    }
+
+    /// Count the record if the shadow config decides differently than the
+    /// active one, which `enabled` it
+    fn compare_shadow(&self, info: &Record, enabled: bool) {
+        let shadow = self.shadow.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ref directives) = *shadow {
+            if self.enabled_by(directives, info.level(), info.module()) != enabled {
+                self.shadow_diffs.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Whether `module` is on the allowlist, if there's one
+    fn allowed(&self, module: &str) -> bool {
+        self.allowlist.as_ref().is_none_or(|allowlist| {
+            allowlist.iter().any(|name| self.module_matches(module, name))
+        })
+    }
+
+    fn directives_enable(&self, directives: &[LogDirective], level: Level, module: &str) -> bool {
+        match self.directive_for(directives, module) {
+            // Only directives naming modules lift the depth cap
+            Some(directive) if directive.name.is_none() && directive.matcher.is_none() =>
+                !self.too_deep(module) && directive.enables(level),
+            Some(directive) => directive.enables(level),
+            None => false,
+        }
+    }
+
+    fn too_deep(&self, module: &str) -> bool {
+        self.max_depth.is_some_and(|n| module.split("::").count() > n)
+    }
+
+    /// `level`, or `Off` if it doesn't come from a directive naming a module
+    /// and `module` is nested deeper than `max_depth`
+    fn capped(&self, level: FilterLevel, named: bool, module: &str) -> FilterLevel {
+        if !named && self.too_deep(module) {
+            FilterLevel::Off
+        } else {
+            level
+        }
+    }
+
+    fn above_floor(&self, level: Level) -> bool {
+        self.min_floor.is_some_and(|floor| level.is_at_least(floor))
+    }
+
+    /// Directive deciding the level of `module`, according to the match mode
+    fn directive_for<'a>(&self, directives: &'a [LogDirective], module: &str) -> Option<&'a LogDirective> {
+        // Search for the longest match, the vector is assumed to be pre-sorted,
+        // highest priority last. In `LastWins` mode it's in insertion order
+        // within each priority, so the last match wins.
+        #[cfg(feature = "globset")]
+        let globbed = match self.globs {
+            Some(ref globs) if std::ptr::eq(directives, self.directives.as_slice()) => Some(globs.matches(module)),
+            _ => None,
+        };
+        let mut matching = directives.iter().enumerate().rev().filter(|&(_i, directive)| match (&directive.matcher, &directive.name) {
+            // Records without a module only get global directives
+            _ if module.is_empty() => directive.matcher.is_none() && directive.name.is_none(),
+            #[cfg(feature = "globset")]
+            (Some(_), _) if globbed.as_ref().is_some_and(|globbed| globbed.covers(_i)) =>
+                globbed.as_ref().is_some_and(|globbed| globbed.contains(_i)),
+            (Some(matcher), _) => matcher.matches(module),
+            (None, Some(name)) => self.module_matches(module, name),
+            (None, None) => true,
+        }).map(|(_, directive)| directive);
+        let first = matching.next()?;
+        if self.match_mode == MatchMode::LastWins || first.matcher.is_none() {
+            return Some(first);
+        }
+
+        // Matchers are sorted last, and equally specific, so the level breaks ties
+        let equal = |directive: &&LogDirective| directive.matcher.is_some() && directive.priority == first.priority;
+        Some(matching.take_while(equal).fold(first, |best, directive| {
+            let better = if self.prefer_quieter {
+                directive.level < best.level
+            } else {
+                directive.level > best.level
+            };
+            if better { directive } else { best }
+        }))
+    }
+
+    /// Level of an active elevation matching `module`
+    fn elevated_level(&self, module: &str) -> Option<FilterLevel> {
+        if !self.elevated.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let mut elevation = self.elevation.lock().unwrap_or_else(|e| e.into_inner());
+        let level = match *elevation {
+            Some(ref elevation) if Instant::now() < elevation.until => {
+                return self.directive_for(&elevation.directives, module).map(|directive| {
+                    self.capped(directive.level, directive.name.is_some() || directive.matcher.is_some(), module)
+                });
+            },
+            _ => None,
+        };
+        *elevation = None;
+        self.elevated.store(false, Ordering::Release);
+        level
+    }
+
+    /// Whether the record passes the filter, required prefix, length
+    /// filter, key filters and error filter
+    fn message_matches(&self, info: &Record, val: &OwnedKVList) -> bool {
+        if !self.filter_applies(info.level()) {
+            return true;
+        }
+        let filter = self.filter_for(info.level());
+        if let Some(ref key_filter) = self.key_filter {
+            if !kv::has_key(info, val, &key_filter.key) {
+                return false;
+            }
+        }
+        if let Some(ref kv_threshold) = self.kv_threshold {
+            let value = kv::find_value(info, val, &kv_threshold.key);
+            if !value.is_some_and(|value| kv_threshold.matches(&value)) {
+                return false;
+            }
+        }
+        if self.errors_only && !kv::has_error(info, val) {
+            return false;
+        }
+        if filter.is_none() && self.len_filter.is_none() && self.required_prefix.is_none() {
+            return true;
+        }
+        if self.filter_target == FilterTarget::Module {
+            if !filter.is_none_or(|filter| filter.is_match(info.module())) {
+                return false;
+            }
+            if self.len_filter.is_none() && self.required_prefix.is_none() {
+                return true;
+            }
+            return B::with_buf(|buf| {
+                let _ = write!(buf, "{}", info.msg());
+                if self.strip_ansi && buf.as_str().contains('\x1b') {
+                    strip_ansi(buf);
+                }
+                self.required_prefix.as_ref().is_none_or(|prefix| buf.as_str().starts_with(prefix.as_str())) &&
+                    self.len_filter.is_none_or(|len_filter| len_filter.matches(buf.as_str().len()))
+            });
+        }
+
+        B::with_buf(|buf| {
+            if self.filter_target == FilterTarget::QualifiedMessage {
+                let _ = write!(buf, "{}: ", info.module());
+            }
+            let start = buf.as_str().len();
+            let _ = write!(buf, "{}", info.msg());
+            if self.strip_ansi && buf.as_str().contains('\x1b') {
+                strip_ansi(buf);
+            }
+            if !self.required_prefix.as_ref().is_none_or(|prefix| buf.as_str()[start..].starts_with(prefix.as_str())) {
+                return false;
+            }
+            let len = buf.as_str().len() - start;
+            self.len_filter.is_none_or(|len_filter| len_filter.matches(len)) &&
+                filter.is_none_or(|filter| match self.max_filter_len {
+                    Some(max) if len > max => self.oversized_policy == OversizedPolicy::Pass,
+                    _ => filter.is_match(buf.as_str()),
+                })
+        })
+    }
+
+    /// Regex filter for `level`
+    fn filter_for(&self, level: Level) -> Option<&filter::Filter> {
+        // A filter for the level replaces the general one
+        match self.level_filters.iter().rev().find(|filter| filter.0 == level) {
+            Some(level_filter) => level_filter.1.as_ref(),
+            None => self.general_filter(),
+        }
+    }
+
+    /// The filter, or the default one if there's none
+    fn general_filter(&self) -> Option<&filter::Filter> {
+        self.filter.as_ref().or(self.default_filter.as_ref())
+    }
+
+    /// Whether records at `level` are checked against any filter
+    fn filter_applies(&self, level: Level) -> bool {
+        let any = self.filter_for(level).is_some() || self.len_filter.is_some() || self.required_prefix.is_some() ||
+            self.key_filter.is_some() || self.kv_threshold.is_some() || self.errors_only;
+        any && self.filter_bound.is_none_or(|bound| bound.applies_to(level))
+    }
+
+    /// Level of the longest counted directive matching the record, if it
+    /// has records left and enables one the other directives don't, which
+    /// uses up one of them
+    fn counted_level(&self, info: &Record) -> Option<FilterLevel> {
+        let module = info.module();
+        let (directive, remaining) = self.counted_directives.iter().rev().find(|(directive, remaining)| {
+            remaining.load(Ordering::Relaxed) > 0 &&
+                directive.name.as_ref().is_none_or(|name| self.module_matches(module, name))
+        })?;
+        let level = self.capped(directive.level, directive.name.is_some(), module);
+        if self.enabled(info.level(), module) || !self.override_enables(level, info) {
+            return None;
+        }
+        remaining.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1)).ok()?;
+        Some(level)
+    }
+
+    /// Level of the last key-value directive matching the record
+    fn kv_level(&self, info: &Record, val: &OwnedKVList) -> Option<FilterLevel> {
+        self.kv_directives.iter().rev()
+            .find(|d| kv::find_value(info, val, &d.key).is_some_and(|v| v == d.value))
+            .map(|d| d.level)
+    }
+
+    /// Level of the last thread directive naming the current thread
+    fn thread_level(&self) -> Option<FilterLevel> {
+        if self.thread_directives.is_empty() {
+            return None;
+        }
+        let thread = std::thread::current();
+        let name = thread.name()?;
+        self.thread_directives.iter().rev()
+            .find(|d| d.0 == name)
+            .map(|d| d.1)
+    }
+
+    fn module_matches(&self, module: &str, name: &str) -> bool {
+        // A leading `::` anchors the name at the crate root: it only matches
+        // whole path segments, regardless of `boundary_aware`
+        let (name, anchored) = match name.strip_prefix("::") {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        // Where the matched prefix ends in `module`; with case folding that
+        // isn't always `name.len()`
+        let end = if self.case_insensitive {
+            caseless_prefix(module, name)
+        } else if module.starts_with(name) {
+            Some(name.len())
+        } else {
+            None
+        };
+        let boundary = anchored || (self.boundary_aware && !name.ends_with('_'));
+        match end {
+            Some(end) => !boundary || {
+                let rest = &module[end..];
+                rest.is_empty() || rest.starts_with("::")
+            },
+            None => false,
+        }
+    }
+}
+
+/// The length in bytes of the prefix of `module` that equals `name` once both
+/// are lowercased, if there is one ending on a character boundary
+#[cfg(feature = "std")]
+fn caseless_prefix(module: &str, name: &str) -> Option<usize> {
+    let mut name = name.chars().flat_map(char::to_lowercase).peekable();
+    for (i, c) in module.char_indices() {
+        if name.peek().is_none() {
+            return Some(i);
+        }
+        if !c.to_lowercase().all(|c| name.next() == Some(c)) {
+            return None;
+        }
+    }
+    match name.peek() {
+        None => Some(module.len()),
+        Some(_) => None,
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T : Drain, B : MessageBuf> EnvLogger<T, B>
+where T::Ok : Default {
+    /// Pass a record that passed filtering to its route, or the main drain
+    fn forward(&self, info: &Record, val: &OwnedKVList) -> result::Result<T::Ok, T::Err> {
+        let route = self.routes.iter().rev()
+            .find(|route| self.module_matches(info.module(), &route.0));
+        match route {
+            Some(route) => route.1.log(info, val).map(|()| T::Ok::default()),
+            None => self.drain.log(info, val),
+        }
+    }
+
+    /// Apply the `map_message` transformation, if any, and emit the record
+    fn map_and_emit(&self, info: &Record, val: &OwnedKVList) -> result::Result<T::Ok, T::Err> {
+        match self.map_message {
+            Some(ref map) => with_msg::<B, _, _>(info, |msg| {
+                let rs = RecordStatic {
+                    location: info.location(),
+                    tag: info.tag(),
+                    level: info.level(),
+                };
+                self.emit(&Record::new(&rs, &format_args!("{}", map(msg)), info.kv()), val)
+            }),
+            None => self.emit(info, val),
+        }
+    }
+
+    /// Remember, format and forward a record that passed filtering
+    fn emit(&self, info: &Record, val: &OwnedKVList) -> result::Result<T::Ok, T::Err> {
+        if self.memory_capacity > 0 {
+            self.remember(info);
+        }
+
+        let format = self.formats.iter().rev()
+            .find(|format| self.module_matches(info.module(), &format.0));
+        match format {
+            Some(format) => {
+                let line = format.1.render(info, val);
+                let rs = RecordStatic {
+                    location: info.location(),
+                    tag: info.tag(),
+                    level: info.level(),
+                };
+                let kv = OwnedKVList::from(slog::o!());
+                self.forward(&Record::new(&rs, &format_args!("{}", line), slog::b!()), &kv)
+            },
+            None => self.forward(info, val),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T : Drain, B : MessageBuf> Drain for EnvLogger<T, B>
+where T::Ok : Default {
+    type Err = T::Err;
+    /// The wrapped drain's `Ok`, or its default for records that weren't
+    /// passed to it
+    type Ok = T::Ok;
+    fn log(&self, info: &Record, val : &OwnedKVList) -> result::Result<T::Ok, T::Err> {
+        if self.enabled_flag.as_ref().is_some_and(|flag| !flag.load(Ordering::Relaxed)) {
+            return Ok(T::Ok::default());
+        }
+        if SILENCED.with(|silenced| silenced.get() > 0) {
+            return Ok(T::Ok::default());
+        }
+        if self.stopped.as_ref().is_some_and(|stopped| stopped.load(Ordering::Relaxed)) {
+            return Ok(T::Ok::default());
+        }
+        self.summarize_suppressed();
+
+        // Levels from the record or the thread don't name a module, so like
+        // global directives they're capped by `max_depth`
+        let override_level = self.level_override_key
+            .and_then(|key| kv::find_value(info, val, key))
+            .and_then(|level| parse_filter_level(&level))
+            .or_else(|| self.kv_level(info, val))
+            .or_else(|| self.thread_level())
+            .map(|level| self.capped(level, false, info.module()))
+            .or_else(|| self.elevated_level(info.module()))
+            .or_else(|| self.counted_level(info));
+        let enabled = match override_level {
+            Some(level) => self.override_enables(level, info),
+            None => self.enabled(info.level(), info.module()),
+        };
+        // Overrides apply to the shadow config just the same
+        if override_level.is_none() && self.shadowed.load(Ordering::Acquire) {
+            self.compare_shadow(info, enabled);
+        }
+        if !enabled {
+            self.metrics.dropped_level.fetch_add(1, Ordering::Relaxed);
+            self.count_suppressed(info);
+            return match self.audit_drain {
+                Some(ref d) => self.audit(d, info).map(|()| T::Ok::default()),
+                None => Ok(T::Ok::default()),
+            };
+        }
+
+        if !self.message_matches(info, val) {
+            self.metrics.dropped_filter.fetch_add(1, Ordering::Relaxed);
+            return match self.filter_miss_drain {
+                Some(ref d) => d.log(info, val).map(|()| T::Ok::default()),
+                None => {
+                    self.count_suppressed(info);
+                    Ok(T::Ok::default())
+                },
+            };
+        }
+
+        if let Some(n) = self.sample {
+            if !self.sampled.fetch_add(1, Ordering::Relaxed).is_multiple_of(n as usize) {
+                self.count_suppressed(info);
+                return Ok(T::Ok::default())
+            }
+        }
+
+        let mut annotations = kv::Annotations::default();
+        if let Some(ref backoff) = self.backoff {
+            match self.backoff_skipped(backoff, info) {
+                Some(skipped) => annotations.skipped = skipped,
+                None => {
+                    self.count_suppressed(info);
+                    return Ok(T::Ok::default())
+                },
+            }
+        }
+
+        if let Some(per_second) = self.rate_limit {
+            if !self.within_rate_limit(per_second, nanos(self.created.elapsed())) {
+                self.rate_dropped.fetch_add(1, Ordering::Relaxed);
+                self.count_suppressed(info);
+                return Ok(T::Ok::default())
+            }
+            let dropped = self.rate_dropped.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                static RS: RecordStatic<'static> = slog::record_static!(Level::Warning, "");
+                let msg = format_args!("dropped {} records over the global rate limit", dropped);
+                let _ = self.log_raw(&Record::new(&RS, &msg, slog::b!()), &OwnedKVList::from(slog::o!()));
+            }
+        }
+
+        self.metrics.passed.fetch_add(1, Ordering::Relaxed);
+        if let Some(ref stopped) = self.stopped {
+            if info.level().is_at_least(Level::Error) {
+                stopped.store(true, Ordering::Relaxed);
+            }
+        }
+        #[cfg(feature = "stats")]
+        {
+            let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+            *stats.entry(info.module()).or_insert(0) += 1;
+        }
+
+        if self.annotate_match && override_level.is_none() {
+            annotations.matched_directive = self.matched_directive(info.module())
+                .filter(|(_, directive)| directive.enables(info.level()))
+                .map(|(_, directive)| directives_to_spec(std::slice::from_ref(&directive), None));
+        }
+
+        if annotations.is_empty() {
+            return self.map_and_emit(info, val);
+        }
+        let rs = RecordStatic {
+            location: info.location(),
+            tag: info.tag(),
+            level: info.level(),
+        };
+        let kv = (info.kv(), annotations);
+        self.map_and_emit(&Record::new(&rs, info.msg(), BorrowedKV(&kv)), val)
+    }
+}
+
+/// Create a `EnvLogger` using `RUST_LOG` environment variable
+#[cfg(feature = "std")]
+pub fn new<T : Drain>(d : T) -> EnvLogger<T> {
+    EnvLogger::new(d)
+}
+
+/// Check a logging specification, without applying it
+///
+/// Unlike `LogBuilder::parse`, which skips invalid parts, this reports every
+/// problem found, including a filter that doesn't compile.
+#[cfg(feature = "std")]
+pub fn validate_spec(spec: &str) -> result::Result<(), Vec<ParseError>> {
+    let ParsedSpec { filter, mut errors, .. } = parse_spec(spec);
+    compile_filter(filter, &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Sort directives by length of their name, this allows a little more
+/// efficient lookup at runtime. Global directives go first, so they're only
+/// used if no named one (not even an empty one) matches. Directives with a
+/// custom matcher go last, to take precedence. Directives of a higher
+/// priority go after all of a lower one, also with `MatchMode::LastWins`,
+/// which otherwise keeps them in order.
+#[cfg(feature = "std")]
+fn sort_directives(directives: &mut [LogDirective], match_mode: MatchMode) {
+    match match_mode {
+        MatchMode::LongestPrefix =>
+            directives.sort_by_key(|d| (d.priority, d.matcher.is_some(), d.name.as_ref().map(|name| name.len()))),
+        MatchMode::LastWins => directives.sort_by_key(|d| d.priority),
+    }
+}
+
+#[cfg(feature = "std")]
+fn print_warnings(errors: &[ParseError]) {
+    for e in errors {
+        println!("warning: {}", e);
+    }
+}
+
+/// Push `message` to the collector of `LogBuilder::collect_warnings`, or
+/// print it if there's none
+#[cfg(feature = "std")]
+fn report_warning(warnings: Option<&Mutex<Vec<String>>>, message: String) {
+    match warnings {
+        Some(warnings) => warnings.lock().unwrap_or_else(|e| e.into_inner()).push(message),
+        None => println!("warning: {}", message),
+    }
+}
+
+/// Directive for a module and level from a TOML table
+#[cfg(feature = "toml")]
+fn toml_directive(name: Option<&str>, level: &str) -> result::Result<LogDirective, ParseError> {
+    let (level, levels) = parse::parse_level(level).ok_or_else(|| ParseError::InvalidLevel(level.to_string()))?;
+    Ok(LogDirective {
+        name: name.map(|name| name.to_string()),
+        level,
+        levels,
+        matcher: None,
+        priority: 0,
+    })
+}
+
+/// Whether `name` is a module path, like `myapp::db`, optionally anchored
+/// with a leading `::`
+#[cfg(feature = "toml")]
+fn is_module_path(name: &str) -> bool {
+    let name = name.strip_prefix("::").unwrap_or(name);
+    name.split("::").all(|segment| {
+        !segment.is_empty() && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+/// `duration` in nanoseconds, saturating at `u64::MAX`
+#[cfg(feature = "std")]
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().min(u128::from(u64::MAX)) as u64
+}
+
+/// slog's compile-time max level, which the default `Drain::is_enabled`
+/// checks
+#[cfg(feature = "std")]
+fn static_max_level() -> FilterLevel {
+    struct Probe;
+
+    impl Drain for Probe {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, _: &Record, _: &OwnedKVList) -> result::Result<(), Never> {
+            Ok(())
+        }
+    }
+
+    [Level::Trace, Level::Debug, Level::Info, Level::Warning, Level::Error, Level::Critical].iter()
+        .find(|&&level| Probe.is_enabled(level))
+        .and_then(|level| FilterLevel::from_usize(level.as_usize()))
+        .unwrap_or(FilterLevel::Off)
+}
+
+/// Compile a filter kept for `LogBuilder::lazy_filter`, dropping it if it's
+/// invalid
+#[cfg(feature = "std")]
+fn compile_now(filter: Option<filter::Filter>, errors: &mut Vec<ParseError>) -> Option<filter::Filter> {
+    compile_filter(filter.map(|filter| filter.source().to_string()), errors)
+}
+
+#[cfg(feature = "std")]
+fn compile_filter(filter: Option<String>, errors: &mut Vec<ParseError>) -> Option<filter::Filter> {
+    filter.and_then(|filter| {
+        match filter::Filter::new(&filter) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                errors.push(ParseError::InvalidFilter(e));
+                None
+            }
+        }
+    })
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use slog::{Level, FilterLevel};
+    use super::slog;
+    use std::{env, thread};
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use super::{LogBuilder, EnvLogger, LogDirective, ParseError, ParsedSpec, Color, FilterBound,
+                Comparison, LenFilter, KeyPresenceFilter, KvThresholdFilter, Env, MatchMode, EmptyModulePolicy, OversizedPolicy,
+                filter, compile_filter, print_warnings, parse_spec, validate_spec,
+                directives_to_spec};
+
+    fn parse_logging_spec(spec: &str) -> (Vec<LogDirective>, Option<filter::Filter>) {
+        let ParsedSpec { directives, filter, mut errors, .. } = parse_spec(spec);
+        let filter = compile_filter(filter, &mut errors);
+        print_warnings(&errors);
+        (directives, filter)
+    }
+
+    /// Drain collecting the messages of all records it receives
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<String>>>);
+
+    impl Capture {
+        fn messages(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl slog::Drain for Capture {
+        type Ok = ();
+        type Err = slog::Never;
+        fn log(&self, info: &slog::Record, _: &slog::OwnedKVList) -> Result<(), slog::Never> {
+            self.0.lock().unwrap().push(format!("{}", info.msg()));
+            Ok(())
+        }
+    }
+
+    fn make_logger(dirs: Vec<LogDirective>) -> EnvLogger<slog::Discard> {
+        let mut logger = LogBuilder::new(slog::Discard).build();
+        logger.directives = dirs;
+        logger
+    }
+
+    #[test]
+    fn filter_info() {
+        let logger = LogBuilder::new(slog::Discard).filter(None, FilterLevel::Info).build();
+        assert!(logger.enabled(Level::Info, "crate1"));
+        assert!(!logger.enabled(Level::Debug, "crate1"));
+    }
+
+    #[test]
+    fn filter_beginning_longest_match() {
+        let logger = LogBuilder::new(slog::Discard)
+                        .filter(Some("crate2"), FilterLevel::Info)
+                        .filter(Some("crate2::mod"), FilterLevel::Debug)
+                        .filter(Some("crate1::mod1"), FilterLevel::Warning)
+                        .build();
+        assert!(logger.enabled(Level::Debug, "crate2::mod1"));
+        assert!(!logger.enabled(Level::Debug, "crate2"));
+    }
+
+    #[test]
+    fn explain() {
+        let logger = LogBuilder::new(slog::Discard)
+                        .filter(Some("crate2"), FilterLevel::Info)
+                        .filter(Some("crate2::mod"), FilterLevel::Debug)
+                        .filter(Some("crate1::mod1"), FilterLevel::Warning)
+                        .build();
+        let explanation = logger.explain(Level::Debug, "crate2::mod1");
+        let (index, directive) = explanation.directive.unwrap();
+        assert_eq!(directive.name(), Some("crate2::mod"));
+        assert_eq!(directive.level(), FilterLevel::Debug);
+        assert_eq!(logger.directives[index], directive);
+        assert!(explanation.enabled);
+        assert!(!explanation.filtered);
+
+        let explanation = logger.explain(Level::Debug, "crate2");
+        assert_eq!(explanation.directive.unwrap().1.name(), Some("crate2"));
+        assert!(!explanation.enabled);
+
+        assert_eq!(logger.explain(Level::Error, "crate3"), super::Explanation {
+            directive: None,
+            enabled: false,
+            filtered: false,
+        });
+
+        let logger = LogBuilder::new(slog::Discard).parse("info/foo@>=warn").build();
+        assert!(logger.explain(Level::Warning, "crate1").filtered);
+        assert!(!logger.explain(Level::Info, "crate1").filtered);
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn build_with_watch() {
+        let (logger, sender) = LogBuilder::new(slog::Discard).parse("info").build_with_watch();
+        assert!(logger.enabled(Level::Info, "crate1"));
+        assert!(!logger.enabled(Level::Debug, "crate1"));
+
+        // Sending doesn't need a runtime, so this is what an async task does
+        sender.send(super::Directives::parse("warn,crate1=debug")).unwrap();
+        assert!(logger.enabled(Level::Debug, "crate1"));
+        assert!(!logger.enabled(Level::Info, "crate2"));
+
+        // Forks start from the directives received last
+        let fork = logger.fork("crate2=error");
+        assert!(fork.enabled(Level::Debug, "crate1"));
+        assert!(!fork.enabled(Level::Warning, "crate2"));
+    }
+
+    #[test]
+    fn metrics() {
+        let logger = Arc::new(LogBuilder::new(slog::Discard).parse("info/foo").build());
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+        slog::info!(log, "foo");
+        slog::warn!(log, "foo bar");
+        slog::info!(log, "bar");
+        slog::debug!(log, "foo");
+        assert_eq!(logger.metrics(), super::Metrics {
+            passed: 2,
+            dropped_level: 1,
+            dropped_filter: 1,
+        });
+
+        logger.reset_counters();
+        assert_eq!(logger.metrics(), super::Metrics::default());
+    }
+
+    #[test]
+    fn global_rate_limit() {
+        let capture = Capture::default();
+        let logger = Arc::new(LogBuilder::new(capture.clone()).parse("info").global_rate_limit(3).build());
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+        for i in 0..10 {
+            slog::info!(log, "burst {}", i);
+        }
+        assert_eq!(capture.messages(), vec!["burst 0", "burst 1", "burst 2"]);
+
+        // Refill the bucket, as if a second had passed
+        logger.rate_full_at.store(0, Ordering::Relaxed);
+        slog::info!(log, "calm");
+        assert_eq!(capture.messages()[3..], [
+            "dropped 7 records over the global rate limit".to_string(),
+            "calm".to_string(),
+        ]);
+
+        // Tokens come back one by one
+        let second = 1_000_000_000;
+        let logger = LogBuilder::new(slog::Discard).global_rate_limit(2).build();
+        assert!(logger.within_rate_limit(2, second - 1));
+        assert!(logger.within_rate_limit(2, second - 1));
+        assert!(!logger.within_rate_limit(2, second - 1));
+        // No second burst at the turn of a second
+        assert!(!logger.within_rate_limit(2, second));
+        assert!(logger.within_rate_limit(2, second - 1 + second / 2));
+        assert!(!logger.within_rate_limit(2, second - 1 + second / 2));
+        assert!(logger.within_rate_limit(2, 3 * second));
+        assert!(logger.within_rate_limit(2, 3 * second));
+        assert!(!logger.within_rate_limit(2, 3 * second));
+
+        // Dropped records show up in suppression summaries
+        let capture = Capture::default();
+        let logger = Arc::new(LogBuilder::new(capture.clone()).parse("info")
+            .global_rate_limit(1)
+            .suppression_summary(Duration::from_secs(3600))
+            .build());
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+        for i in 0..3 {
+            slog::info!(log, "burst {}", i);
+        }
+        logger.summary_due.store(0, Ordering::Relaxed);
+        slog::info!(log, "due");
+        assert_eq!(capture.messages(), vec![
+            "burst 0".to_string(),
+            format!("suppressed records since last summary: {}=2", module_path!()),
+        ]);
+
+        // No limit
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info").global_rate_limit(0).build();
+        let log = slog::Logger::root(logger, slog::o!());
+        for i in 0..10 {
+            slog::info!(log, "burst {}", i);
+        }
+        assert_eq!(capture.messages().len(), 10);
+    }
+
+    #[test]
+    fn backoff_dedup() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info")
+            .format_for("", super::FormatKind::Full)
+            .backoff_dedup()
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        for i in 0..20 {
+            slog::info!(log, "storm");
+            if i % 5 == 0 {
+                slog::info!(log, "rare {}", i);
+            }
+        }
+        assert_eq!(capture.messages(), vec![
+            "storm", "rare 0", "storm", "storm skipped=1", "rare 5", "storm skipped=3",
+            "rare 10", "storm skipped=7", "rare 15",
+        ]);
+    }
+
+    #[test]
+    fn annotate_match() {
+        mod db {
+            pub fn log(log: &slog::Logger) {
+                slog::debug!(log, "query");
+            }
+        }
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone())
+            .parse(&format!("info,{}::db=debug,@id=1=debug", module_path!()))
+            .format_for("", super::FormatKind::Full)
+            .annotate_match()
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "global");
+        db::log(&log);
+        // Enabled by the key-value directive
+        slog::debug!(log, "by key"; "id" => 1);
+        slog::debug!(log, "dropped");
+        assert_eq!(capture.messages(), vec![
+            "global matched_directive=info".to_string(),
+            format!("query matched_directive={}::db=debug", module_path!()),
+            "by key id=1".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn suppression_summary() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/foo")
+            .suppression_summary(Duration::from_millis(100))
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::debug!(log, "foo");
+        slog::debug!(log, "foo");
+        slog::info!(log, "bar");
+        slog::info!(log, "foo 1");
+        assert_eq!(capture.messages(), vec!["foo 1"]);
+
+        thread::sleep(Duration::from_millis(150));
+        slog::info!(log, "foo 2");
+        assert_eq!(capture.messages(), vec![
+            "foo 1".to_string(),
+            format!("suppressed records since last summary: {}=3", module_path!()),
+            "foo 2".to_string(),
+        ]);
+
+        // Nothing suppressed since
+        thread::sleep(Duration::from_millis(150));
+        slog::info!(log, "foo 3");
+        assert_eq!(capture.messages().len(), 4);
+    }
+
+    #[test]
+    fn non_unit_ok() {
+        use slog::Drain;
+
+        struct Len;
+
+        impl Drain for Len {
+            type Ok = usize;
+            type Err = slog::Never;
+            fn log(&self, info: &slog::Record, _: &slog::OwnedKVList) -> Result<usize, slog::Never> {
+                Ok(info.msg().to_string().len())
+            }
+        }
+
+        let logger = LogBuilder::new(Len).parse("info").build();
+        let kv = slog::OwnedKVList::from(slog::o!());
+        static INFO: slog::RecordStatic<'static> = slog::record_static!(Level::Info, "");
+        static DEBUG: slog::RecordStatic<'static> = slog::record_static!(Level::Debug, "");
+        assert_eq!(logger.log(&slog::Record::new(&INFO, &format_args!("four"), slog::b!()), &kv), Ok(4));
+        assert_eq!(logger.log(&slog::Record::new(&DEBUG, &format_args!("four"), slog::b!()), &kv), Ok(0));
+    }
+
+    #[test]
+    fn no_default() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).no_default().build();
+        assert!(!logger.enabled(Level::Critical, "crate1"));
+        assert!(!logger.enabled(Level::Error, ""));
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::error!(log, "suppressed");
+        assert!(capture.messages().is_empty());
+
+        let logger = LogBuilder::new(slog::Discard).no_default().parse("crate1=info").build();
+        assert!(logger.enabled(Level::Info, "crate1"));
+        assert!(!logger.enabled(Level::Error, "crate2"));
+
+        let logger = LogBuilder::new(slog::Discard).no_default().parse("crate1=+3").build();
+        assert!(logger.enabled(Level::Warning, "crate1"));
+        assert!(!logger.enabled(Level::Info, "crate1"));
+    }
+
+    #[test]
+    fn directive_priority() {
+        let parsed = parse_spec("info,!crate1=warn,!!crate2,!@key=value,!crate3=+1");
+        assert_eq!(parsed.directives.iter().map(|d| d.priority()).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(parsed.errors, vec![ParseError::InvalidDirective("!@key=value".to_string()),
+                                       ParseError::InvalidDirective("!crate3=+1".to_string())]);
+        assert_eq!(super::directives_to_spec(&parsed.directives, None), "info,!crate1=warning,!!crate2=trace");
+
+        let logger = LogBuilder::new(slog::Discard).parse("crate1::mod1=trace,!crate1=warn,info").build();
+        assert!(!logger.enabled(Level::Info, "crate1::mod1"));
+        assert!(logger.enabled(Level::Warning, "crate1::mod1"));
+        assert!(logger.enabled(Level::Info, "crate2"));
+
+        // Ties are broken by length
+        let logger = LogBuilder::new(slog::Discard)
+            .parse("!crate1=warn,!crate1::mod1=debug")
+            .filter_priority(Some("crate1::mod1::a"), FilterLevel::Trace, 0)
+            .build();
+        assert!(logger.enabled(Level::Debug, "crate1::mod1::a"));
+        assert!(!logger.enabled(Level::Trace, "crate1::mod1::a"));
+
+        let logger = LogBuilder::new(slog::Discard)
+            .parse("!crate1=warn,crate1=debug")
+            .match_mode(MatchMode::LastWins)
+            .filter_priority(Some("crate1"), FilterLevel::Error, 2)
+            .build();
+        assert!(!logger.enabled(Level::Warning, "crate1"));
+        assert!(logger.enabled(Level::Error, "crate1"));
+    }
+
+    #[test]
+    fn off_then_enable() {
+        for spec in ["off,app=debug,net=info", "app=debug,net=info,off", "*=off,app=debug,net=info", "0,app=debug,net=info"] {
+            let logger = LogBuilder::new(slog::Discard).parse(spec).build();
+            assert!(logger.enabled(Level::Debug, "app"), "{}", spec);
+            assert!(logger.enabled(Level::Debug, "app::db"), "{}", spec);
+            assert!(!logger.enabled(Level::Trace, "app"), "{}", spec);
+            assert!(logger.enabled(Level::Info, "net::http"), "{}", spec);
+            assert!(!logger.enabled(Level::Debug, "net"), "{}", spec);
+            assert!(!logger.enabled(Level::Critical, "other"), "{}", spec);
+            assert!(!logger.enabled(Level::Critical, ""), "{}", spec);
+            assert_eq!(logger.filter(), FilterLevel::Debug, "{}", spec);
+        }
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse(&format!("off,{}::app=debug", module_path!())).build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::crit!(log, "suppressed");
+        mod app {
+            pub fn log(log: &super::slog::Logger) {
+                super::slog::debug!(log, "enabled");
+            }
+        }
+        app::log(&log);
+        assert_eq!(capture.messages(), vec!["enabled"]);
+    }
+
+    #[test]
+    fn relative_directives() {
+        let parsed = parse_spec("info,noisy=-1,important=+2,bad=+x");
+        assert_eq!(parsed.relative_directives.len(), 2);
+        assert_eq!(parsed.relative_directives[0].offset(), -1);
+        assert_eq!(parsed.errors, vec![ParseError::InvalidLevel("+x".to_string())]);
+
+        let logger = LogBuilder::new(slog::Discard).parse("info,noisy=-1,important=+2").build();
+        assert!(logger.enabled(Level::Warning, "noisy"));
+        assert!(!logger.enabled(Level::Info, "noisy"));
+        assert!(logger.enabled(Level::Trace, "important"));
+        assert!(logger.enabled(Level::Info, "other"));
+        assert!(!logger.enabled(Level::Debug, "other"));
+
+        // Clamped at the extremes
+        let logger = LogBuilder::new(slog::Discard).parse("debug,quiet=-9,loud=+9").build();
+        assert_eq!(logger.directive_for(&logger.directives, "quiet").map(|d| d.level), Some(FilterLevel::Off));
+        assert_eq!(logger.directive_for(&logger.directives, "loud").map(|d| d.level), Some(FilterLevel::Trace));
+
+        // Without a global directive, relative to the default of error
+        let logger = LogBuilder::new(slog::Discard).parse("crate1=+1").build();
+        assert!(logger.enabled(Level::Warning, "crate1"));
+        assert!(!logger.enabled(Level::Warning, "crate2"));
+
+        // `*` adjusts the global level
+        let parsed = parse_spec("info,*=+1,crate1=-1");
+        assert_eq!(parsed.relative_directives[0].name(), None);
+        assert_eq!(directives_to_spec(&parsed.resolved_directives(FilterLevel::Error), None),
+                   "info,debug,crate1=warning");
+        let logger = LogBuilder::new(slog::Discard).parse("info,*=+1").build();
+        assert!(logger.enabled(Level::Debug, "crate2"));
+
+        // Elevations are relative to the configured level
+        let logger = LogBuilder::new(slog::Discard).parse("warn").build();
+        logger.elevate_temporarily("crate1=+1", Duration::from_secs(60));
+        assert_eq!(logger.elevated_level("crate1"), Some(FilterLevel::Info));
+    }
+
+    #[test]
+    fn collect_warnings() {
+        let (builder, warnings) = LogBuilder::new(slog::Discard).collect_warnings();
+        let _ = builder.parse("info,crate1=nope").parse("crate2=a=b").build();
+        assert_eq!(*warnings.lock().unwrap(), vec![
+            "invalid logging spec 'nope', ignoring it".to_string(),
+            "invalid logging spec 'crate2=a=b', ignoring it".to_string(),
+        ]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "regex", feature = "regex-lite"))]
+    fn filter_target() {
+        mod http {
+            pub fn log(log: &slog::Logger) {
+                slog::info!(log, "request timeout");
+            }
+        }
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone())
+            .parse("info/::http: .*timeout")
+            .filter_target(super::FilterTarget::QualifiedMessage)
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        http::log(&log);
+        slog::info!(log, "request timeout");
+        slog::info!(log, "http: request timeout");
+        assert_eq!(capture.messages(), vec!["request timeout"]);
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone())
+            .parse("info/::http$@len<20")
+            .filter_target(super::FilterTarget::Module)
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        http::log(&log);
+        slog::info!(log, "http");
+        assert_eq!(capture.messages(), vec!["request timeout"]);
+    }
+
+    #[test]
+    fn default_filter() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).default_filter("foo").parse("info").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "foo");
+        slog::info!(log, "bar");
+        assert_eq!(capture.messages(), vec!["foo"]);
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).default_filter("foo").parse("info/bar").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "foo");
+        slog::info!(log, "bar");
+        assert_eq!(capture.messages(), vec!["bar"]);
+    }
+
+    #[test]
+    fn filter_with_slashes() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info//api/v1").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "GET /api/v1/users");
+        slog::info!(log, "GET /api/v2/users");
+        slog::info!(log, "api/v1");
+        assert_eq!(capture.messages(), vec!["GET /api/v1/users"]);
+    }
+
+    #[test]
+    fn from_directives() {
+        let logger = LogBuilder::from_directives(vec![
+            LogDirective::new(Some("crate1::mod1"), FilterLevel::Warning),
+            LogDirective::new(None, FilterLevel::Info),
+        ]).build();
+        assert!(logger.would_log(Level::Info, "crate2"));
+        assert!(!logger.would_log(Level::Info, "crate1::mod1"));
+        assert!(logger.would_log(Level::Warning, "crate1::mod1"));
+
+        let logger = LogBuilder::from_directives(Vec::new()).build();
+        assert!(logger.would_log(Level::Error, "crate1"));
+        assert!(!logger.would_log(Level::Warning, "crate1"));
+    }
+
+    #[test]
+    fn memory_buffer() {
+        let logger = LogBuilder::new(slog::Discard).parse("info").memory_buffer(3).build();
+        assert!(logger.recent_logs().is_empty());
+        let logger = Arc::new(logger);
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+        for i in 0..5 {
+            slog::info!(log, "line {}", i);
+            slog::debug!(log, "hidden {}", i);
+        }
+        let module = module_path!();
+        assert_eq!(logger.recent_logs(), vec![
+            format!("INFO {}: line 2", module),
+            format!("INFO {}: line 3", module),
+            format!("INFO {}: line 4", module),
+        ]);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn async_inside_env_logger() {
+        extern crate slog_async;
+        use slog::Drain;
+
+        // Filtering before the async drain, so only passing records are sent
+        // to its thread
+        let capture = Capture::default();
+        let drain = slog_async::Async::default(capture.clone());
+        let log = slog::Logger::root(LogBuilder::new(drain).parse("info").build().fuse(), slog::o!());
+        for i in 0..100 {
+            slog::debug!(log, "suppressed {}", i);
+        }
+        slog::info!(log, "passed");
+        // Dropping the async drain waits for its thread to finish
+        drop(log);
+        assert_eq!(capture.messages(), vec!["passed"]);
+    }
+
+    #[test]
+    fn filters() {
+        let logger = LogBuilder::new(slog::Discard).filters(&[
+            (None, FilterLevel::Warning),
+            (Some("crate1".to_string()), FilterLevel::Debug),
+            (Some("crate1::mod1".to_string()), FilterLevel::Off),
+        ]).build();
+        assert!(logger.enabled(Level::Warning, "crate2"));
+        assert!(!logger.enabled(Level::Info, "crate2"));
+        assert!(logger.enabled(Level::Debug, "crate1"));
+        assert!(!logger.enabled(Level::Critical, "crate1::mod1"));
+    }
+
+    #[test]
+    fn module_matchers() {
+        use super::{ExactMatcher, GlobMatcher, ModuleMatcher, SuffixMatcher};
+
+        assert!(ExactMatcher("crate1".to_string()).matches("crate1"));
+        assert!(!ExactMatcher("crate1".to_string()).matches("crate1::mod1"));
+        assert!(SuffixMatcher("::tests".to_string()).matches("crate1::mod1::tests"));
+        assert!(!SuffixMatcher("::tests".to_string()).matches("crate1::tests::mod1"));
+        let glob = GlobMatcher("*::db::*".to_string());
+        assert!(glob.matches("crate1::db::pool"));
+        assert!(glob.matches("crate1::mod1::db::"));
+        assert!(!glob.matches("crate1::db"));
+        assert!(GlobMatcher("crate?::*o*l".to_string()).matches("crate1::db::pool"));
+        assert!(!GlobMatcher("crate?".to_string()).matches("crate12"));
+
+        #[derive(Debug)]
+        struct Depth(usize);
+
+        impl ModuleMatcher for Depth {
+            fn matches(&self, module: &str) -> bool {
+                module.split("::").count() > self.0
+            }
+        }
+
+        let logger = LogBuilder::new(slog::Discard)
+            .parse("info,crate1::mod1::sub=debug")
+            .filter_matcher(Depth(2), FilterLevel::Warning)
+            .filter_matcher(ExactMatcher("crate1::mod1::exact".to_string()), FilterLevel::Trace)
+            .build();
+        assert!(logger.enabled(Level::Info, "crate1::mod1"));
+        assert!(!logger.enabled(Level::Info, "crate1::mod1::sub"));
+        assert!(logger.enabled(Level::Warning, "crate2::mod1::sub"));
+        assert!(logger.enabled(Level::Trace, "crate1::mod1::exact"));
+    }
+
+    #[test]
+    fn match_mode() {
+        let spec = "crate1::mod1=warn,crate1=debug";
+        let logger = LogBuilder::new(slog::Discard).parse(spec).build();
+        assert!(!logger.enabled(Level::Info, "crate1::mod1"));
+        assert!(logger.enabled(Level::Info, "crate1::mod2"));
+
+        let logger = LogBuilder::new(slog::Discard).parse(spec).match_mode(MatchMode::LastWins).build();
+        assert!(logger.enabled(Level::Info, "crate1::mod1"));
+        assert!(logger.enabled(Level::Info, "crate1::mod2"));
+
+        let logger = LogBuilder::new(slog::Discard).parse("crate1=debug,crate1::mod1=warn")
+            .match_mode(MatchMode::LastWins).build();
+        assert!(!logger.enabled(Level::Info, "crate1::mod1"));
+        assert!(logger.enabled(Level::Info, "crate1::mod2"));
+    }
+
+    #[test]
+    fn case_insensitive_modules() {
+        let logger = LogBuilder::new(slog::Discard).parse("MyCrate=debug").build();
+        assert!(!logger.enabled(Level::Debug, "mycrate"));
+
+        let logger = LogBuilder::new(slog::Discard)
+                        .parse("MyCrate=debug")
+                        .case_insensitive_modules()
+                        .build();
+        assert!(logger.enabled(Level::Debug, "mycrate"));
+        assert!(logger.enabled(Level::Debug, "MYCRATE::mod1"));
+        assert!(!logger.enabled(Level::Debug, "othercrate"));
+    }
+
+    #[test]
+    fn unicode_modules() {
+        let logger = LogBuilder::new(slog::Discard)
+                        .parse("warn,crate_é=debug,Ünï=info")
+                        .build();
+        assert!(logger.enabled(Level::Debug, "crate_é"));
+        assert!(logger.enabled(Level::Debug, "crate_é::mod1"));
+        assert!(!logger.enabled(Level::Debug, "crate_e"));
+        assert!(!logger.enabled(Level::Debug, "crate"));
+        assert!(!logger.enabled(Level::Info, "ünï"));
+        // Differs from the directive only in the last byte of a character
+        assert!(!logger.enabled(Level::Debug, "crate_\u{e8}"));
+
+        let logger = LogBuilder::new(slog::Discard)
+                        .parse("warn,crate_é=debug,Ünï=info,Straße=debug")
+                        .case_insensitive_modules()
+                        .boundary_aware()
+                        .build();
+        assert!(logger.enabled(Level::Debug, "CRATE_É::mod1"));
+        assert!(!logger.enabled(Level::Debug, "crate_éé"));
+        assert!(logger.enabled(Level::Info, "ünï"));
+        assert!(logger.enabled(Level::Info, "ÜNÏ::mod1"));
+        assert!(!logger.enabled(Level::Info, "ün"));
+        assert!(logger.enabled(Level::Debug, "STRAẞE"));
+        assert!(!logger.enabled(Level::Debug, "STRASSE"));
+    }
+
+    #[test]
+    fn empty_module_policy() {
+        let spec = "warn,crate1=debug";
+        let logger = LogBuilder::new(slog::Discard).parse(spec)
+            .filter_matcher(super::GlobMatcher("*".to_string()), FilterLevel::Trace)
+            .build();
+        assert!(logger.enabled(Level::Warning, ""));
+        assert!(!logger.enabled(Level::Info, ""));
+        assert!(logger.enabled(Level::Trace, "crate1"));
+
+        let logger = LogBuilder::new(slog::Discard).parse(spec)
+            .empty_module_policy(EmptyModulePolicy::AlwaysSuppress)
+            .min_floor(Level::Error)
+            .build();
+        assert!(!logger.enabled(Level::Critical, ""));
+        assert!(logger.enabled(Level::Warning, "crate1"));
+
+        let logger = LogBuilder::new(slog::Discard).parse("off")
+            .empty_module_policy(EmptyModulePolicy::AlwaysLog)
+            .build();
+        assert!(logger.enabled(Level::Debug, ""));
+        assert!(!logger.enabled(Level::Critical, "crate1"));
+    }
+
+    #[test]
+    fn overrides_respect_module_gates() {
+        mod app {
+            pub fn log(log: &slog::Logger) {
+                slog::info!(log, "too deep");
+            }
+        }
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("off,@build=canary=debug")
+            .empty_module_policy(EmptyModulePolicy::AlwaysSuppress)
+            .max_depth(2)
+            .build();
+        static LOCATION: slog::RecordLocation = slog::RecordLocation {
+            file: "",
+            line: 0,
+            column: 0,
+            function: "",
+            module: "",
+        };
+        static RS: slog::RecordStatic<'static> = slog::RecordStatic {
+            location: &LOCATION,
+            tag: "",
+            level: Level::Info,
+        };
+        let kv = slog::OwnedKVList::from(slog::o!("build" => "canary"));
+        slog::Drain::log(&logger, &slog::Record::new(&RS, &format_args!("no module"), slog::b!()), &kv).unwrap();
+        let log = slog::Logger::root(logger, slog::o!("build" => "canary"));
+        app::log(&log);
+        slog::info!(log, "logged");
+        assert_eq!(capture.messages(), vec!["logged"]);
+    }
+
+    #[test]
+    fn filter_source() {
+        let logger = LogBuilder::new(slog::Discard).parse("info").build();
+        assert_eq!(logger.filter_source(), None);
+
+        let pattern = "^(?x) foo \\d{1,2}  |bar$";
+        let logger = LogBuilder::new(slog::Discard).parse(&format!("info/{}", pattern)).build();
+        assert_eq!(logger.filter_source(), Some(pattern));
+
+        let logger = LogBuilder::new(slog::Discard).default_filter(pattern).build();
+        assert_eq!(logger.filter_source(), Some(pattern));
+    }
+
+    #[test]
+    fn filter_matches() {
+        let logger = LogBuilder::new(slog::Discard).parse("info").build();
+        assert_eq!(logger.filter_matches("anything"), None);
+
+        let logger = LogBuilder::new(slog::Discard).parse("info/connect").build();
+        assert_eq!(logger.filter_matches("connect 42 times"), Some(true));
+        assert_eq!(logger.filter_matches("conn 1"), Some(false));
+
+        #[cfg(any(feature = "regex", feature = "regex-lite"))]
+        {
+            let logger = LogBuilder::new(slog::Discard).parse("info/^conn(ect)? \\d+").build();
+            assert_eq!(logger.filter_matches("conn 1"), Some(true));
+            assert_eq!(logger.filter_matches("disconnect 42"), Some(false));
+
+            let logger = LogBuilder::new(slog::Discard).parse("info/^red$").strip_ansi_for_filter().build();
+            assert_eq!(logger.filter_matches("\x1b[31mred\x1b[0m"), Some(true));
+        }
+    }
+
+    #[test]
+    fn boundary_aware() {
+        let logger = LogBuilder::new(slog::Discard).parse("app=debug").build();
+        assert!(logger.enabled(Level::Debug, "apple"));
+
+        let logger = LogBuilder::new(slog::Discard).parse("app=debug").boundary_aware().build();
+        assert!(logger.enabled(Level::Debug, "app"));
+        assert!(logger.enabled(Level::Debug, "app::x"));
+        assert!(!logger.enabled(Level::Debug, "apple"));
+        assert!(!logger.enabled(Level::Debug, "app:x"));
+    }
+
+    #[test]
+    fn boundary_aware_crate_prefix() {
+        let logger = LogBuilder::new(slog::Discard).parse("myorg_=debug,myorg_foo::db=warn")
+            .boundary_aware().build();
+        assert!(logger.enabled(Level::Debug, "myorg_foo"));
+        assert!(logger.enabled(Level::Debug, "myorg_bar::api"));
+        assert!(!logger.enabled(Level::Debug, "myorg_foo::db"));
+        assert!(!logger.enabled(Level::Debug, "myorg"));
+        assert!(!logger.enabled(Level::Debug, "myorgfoo"));
+
+        let logger = LogBuilder::new(slog::Discard).parse("myorg=debug").boundary_aware().build();
+        assert!(!logger.enabled(Level::Debug, "myorg_foo"));
+    }
+
+    #[test]
+    fn anchored_directive() {
+        let logger = LogBuilder::new(slog::Discard).parse("::app=debug,::app::net=warn,my_=trace").build();
+        assert!(logger.enabled(Level::Debug, "app"));
+        assert!(logger.enabled(Level::Debug, "app::x"));
+        assert!(logger.enabled(Level::Debug, "app::network"));
+        assert!(!logger.enabled(Level::Debug, "app::net::tcp"));
+        assert!(!logger.enabled(Level::Debug, "apple"));
+        // Unanchored names keep matching textually
+        assert!(logger.enabled(Level::Trace, "my_crate"));
+
+        let logger = LogBuilder::new(slog::Discard).parse("::my_=debug").build();
+        assert!(!logger.enabled(Level::Debug, "my_crate"));
+        assert_eq!(logger.directives[0].name(), Some("::my_"));
+    }
+
+    #[test]
+    fn min_level_and_compiletime_floor() {
+        let logger = LogBuilder::new(slog::Discard).parse("warn,crate1=debug").build();
+        assert_eq!(logger.min_level(), FilterLevel::Warning);
+        assert_eq!(logger.filter(), FilterLevel::Debug);
+
+        let (builder, warnings) = LogBuilder::new(slog::Discard).collect_warnings();
+        builder.parse("error").build();
+        assert!(warnings.lock().unwrap().is_empty());
+
+        // slog compiles out trace records by default
+        let (builder, warnings) = LogBuilder::new(slog::Discard).collect_warnings();
+        builder.parse("info,crate1=trace").build();
+        if cfg!(debug_assertions) {
+            assert_eq!(*warnings.lock().unwrap(), vec![
+                format!("logging spec enables level {}, but slog is compiled with max level {}",
+                        FilterLevel::Trace, super::static_max_level()),
+            ]);
+        }
+
+        // The floor counts like for `filter`
+        let logger = LogBuilder::new(slog::Discard).parse("off,crate1=debug").min_floor(Level::Error).build();
+        assert_eq!(logger.min_level(), FilterLevel::Error);
+
+        let logger = LogBuilder::new(slog::Discard).parse("info,crate1=debug").build().with_compiletime_floor();
+        assert_eq!(logger.filter(), FilterLevel::Debug);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "slog is compiled with max level")]
+    fn compiletime_floor_mismatch() {
+        LogBuilder::new(slog::Discard).parse("trace").build().with_compiletime_floor();
+    }
+
+    #[test]
+    fn level_override_key() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone())
+                        .parse("info")
+                        .level_override_key("log_level")
+                        .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::debug!(log, "root");
+        let request = log.new(slog::o!("log_level" => "trace"));
+        slog::debug!(request, "request");
+        slog::debug!(log, "other"; "log_level" => "debug");
+        slog::debug!(log, "unparseable"; "log_level" => "loud");
+        assert_eq!(capture.messages(), vec!["request", "other"]);
+    }
+
+    #[test]
+    fn kv_directives() {
+        let parsed = parse_spec("info,@build=8jdkj2df=debug,@build,@version=1=nope");
+        assert_eq!(parsed.directives.len(), 1);
+        assert_eq!(parsed.kv_directives.len(), 1);
+        assert_eq!(parsed.kv_directives[0].key(), "build");
+        assert_eq!(parsed.kv_directives[0].value(), "8jdkj2df");
+        assert_eq!(parsed.kv_directives[0].level(), FilterLevel::Debug);
+        assert_eq!(parsed.errors, vec![
+            ParseError::InvalidDirective("@build".to_string()),
+            ParseError::InvalidLevel("nope".to_string()),
+        ]);
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("off,@build=8jdkj2df=debug").build();
+        let root = slog::Logger::root(logger, slog::o!());
+        let canary = root.new(slog::o!("build" => "8jdkj2df", "version" => "0.1.5"));
+        let stable = root.new(slog::o!("build" => "1a2b3c4d", "version" => "0.1.4"));
+        slog::debug!(canary, "canary");
+        slog::error!(stable, "stable");
+        slog::info!(root, "no build"; "build" => "8jdkj2df");
+        assert_eq!(capture.messages(), vec!["canary", "no build"]);
+    }
+
+    #[test]
+    fn thread_directives() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info")
+            .filter_thread("worker-2", FilterLevel::Debug)
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        let workers = ["worker-1", "worker-2"].iter().map(|name| {
+            let log = log.clone();
+            thread::Builder::new().name(name.to_string()).spawn(move || {
+                slog::debug!(log, "debug from {}", thread::current().name().unwrap());
+                slog::info!(log, "info from {}", thread::current().name().unwrap());
+            }).unwrap()
+        }).collect::<Vec<_>>();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        slog::debug!(log, "debug from main");
+
+        let mut messages = capture.messages();
+        messages.sort();
+        assert_eq!(messages, vec!["debug from worker-2", "info from worker-1", "info from worker-2"]);
+
+        // Capped like a global directive
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info")
+            .filter_thread("worker", FilterLevel::Debug)
+            .max_depth(1)
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        thread::Builder::new().name("worker".to_string()).spawn(move || {
+            slog::info!(log, "too deep");
+        }).unwrap().join().unwrap();
+        assert!(capture.messages().is_empty());
+    }
+
+    #[test]
+    fn drain_access() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).build();
+        assert!(Arc::ptr_eq(&logger.drain().0, &capture.0));
+
+        let logger = LogBuilder::new(slog::Discard).build();
+        let slog::Discard = logger.into_inner();
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn stats() {
+        mod crate1 {
+            pub fn log(log: &slog::Logger) {
+                slog::info!(log, "crate1");
+            }
+        }
+
+        let logger = Arc::new(LogBuilder::new(slog::Discard).parse("info/crate").build());
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+        for _ in 0..3 {
+            crate1::log(&log);
+        }
+        slog::info!(log, "crate");
+        slog::info!(log, "filtered");
+        slog::debug!(log, "crate");
+
+        let stats = logger.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[module_path!()], 1);
+        assert_eq!(stats[&format!("{}::crate1", module_path!())], 3);
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn reset_counters() {
+        let capture = Capture::default();
+        let logger = Arc::new(LogBuilder::new(capture.clone()).parse("info;sample=2").build());
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+        slog::info!(log, "1");
+        slog::info!(log, "2");
+        slog::info!(log, "3");
+        assert_eq!(logger.stats()[module_path!()], 2);
+
+        logger.reset_counters();
+        assert!(logger.stats().is_empty());
+        slog::info!(log, "4");
+        assert_eq!(capture.messages(), vec!["1", "3", "4"]);
+        assert_eq!(logger.stats()[module_path!()], 1);
+    }
+
+    #[test]
+    fn elevate_temporarily() {
+        let capture = Capture::default();
+        let logger = Arc::new(LogBuilder::new(capture.clone()).parse("info").build());
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+
+        logger.elevate_temporarily(&format!("{}=debug", module_path!()), Duration::from_millis(100));
+        slog::debug!(log, "elevated");
+        assert!(logger.elevated.load(Ordering::SeqCst));
+        thread::sleep(Duration::from_millis(150));
+        slog::debug!(log, "reverted");
+        slog::info!(log, "info");
+        assert!(!logger.elevated.load(Ordering::SeqCst));
+
+        assert_eq!(capture.messages(), vec!["elevated", "info"]);
+    }
+
+    #[test]
+    fn parse_default() {
+        let logger = LogBuilder::new(slog::Discard).parse("info,crate1::mod1=warn").build();
+        assert!(logger.enabled(Level::Warning, "crate1::mod1"));
+        assert!(logger.enabled(Level::Info, "crate2::mod2"));
+    }
+
+    #[test]
+    fn match_full_path() {
+        let logger = make_logger(vec![
+            LogDirective {
+                name: Some("crate2".to_string()),
+                level: FilterLevel::Info,
+                levels: None,
+                matcher: None,
+                priority: 0,
+            },
+            LogDirective {
+                name: Some("crate1::mod1".to_string()),
+                level: FilterLevel::Warning,
+                levels: None,
+                matcher: None,
+                priority: 0,
+            }
+        ]);
+        assert!(logger.enabled(Level::Warning, "crate1::mod1"));
+        assert!(!logger.enabled(Level::Info, "crate1::mod1"));
+        assert!(logger.enabled(Level::Info, "crate2"));
+        assert!(!logger.enabled(Level::Debug, "crate2"));
+    }
+
+    #[test]
+    fn no_match() {
+        let logger = make_logger(vec![
+            LogDirective { name: Some("crate2".to_string()), level: FilterLevel::Info, levels: None, matcher: None, priority: 0 },
+            LogDirective { name: Some("crate1::mod1".to_string()), level: FilterLevel::Warning, levels: None, matcher: None, priority: 0 }
+        ]);
+        assert!(!logger.enabled(Level::Warning, "crate3"));
+    }
+
+    #[test]
+    fn match_beginning() {
+        let logger = make_logger(vec![
+            LogDirective { name: Some("crate2".to_string()), level: FilterLevel::Info, levels: None, matcher: None, priority: 0 },
+            LogDirective { name: Some("crate1::mod1".to_string()), level: FilterLevel::Warning, levels: None, matcher: None, priority: 0 }
+        ]);
+        assert!(logger.enabled(Level::Info, "crate2::mod1"));
+    }
+
+    #[test]
+    fn match_beginning_longest_match() {
+        let logger = make_logger(vec![
+            LogDirective { name: Some("crate2".to_string()), level: FilterLevel::Info, levels: None, matcher: None, priority: 0 },
+            LogDirective { name: Some("crate2::mod".to_string()), level: FilterLevel::Debug, levels: None, matcher: None, priority: 0 },
+            LogDirective { name: Some("crate1::mod1".to_string()), level: FilterLevel::Warning, levels: None, matcher: None, priority: 0 }
+        ]);
+        assert!(logger.enabled(Level::Debug, "crate2::mod1"));
+        assert!(!logger.enabled(Level::Debug, "crate2"));
+    }
+
+    #[test]
+    fn match_default() {
+        let logger = make_logger(vec![
+            LogDirective { name: None, level: FilterLevel::Info, levels: None, matcher: None, priority: 0 },
+            LogDirective { name: Some("crate1::mod1".to_string()), level: FilterLevel::Warning, levels: None, matcher: None, priority: 0 }
+        ]);
+        assert!(logger.enabled(Level::Warning, "crate1::mod1"));
+        assert!(logger.enabled(Level::Info, "crate2::mod2"));
+    }
+
+    #[test]
+    fn match_default_no_named_match() {
+        let logger = LogBuilder::new(slog::Discard)
+                        .filter(Some("crate1::mod1"), FilterLevel::Trace)
+                        .filter(Some("crate2"), FilterLevel::Error)
+                        .filter(None, FilterLevel::Info)
+                        .filter(Some("crate3::a"), FilterLevel::Off)
+                        .build();
+        assert!(logger.enabled(Level::Info, "crate4"));
+        assert!(!logger.enabled(Level::Debug, "crate4"));
+        assert!(logger.enabled(Level::Info, "crate1::mod2"));
+        assert!(logger.enabled(Level::Info, "crate3::b"));
+        assert!(logger.enabled(Level::Info, ""));
+        assert!(logger.enabled(Level::Trace, "crate1::mod1"));
+        assert!(!logger.enabled(Level::Info, "crate2"));
+    }
+
+    #[test]
+    fn match_default_after_empty_name() {
+        // An empty module name matches every module, and has to take
+        // precedence over the global directive regardless of their order
+        let logger = LogBuilder::new(slog::Discard)
+                        .filter(Some(""), FilterLevel::Debug)
+                        .filter(None, FilterLevel::Error)
+                        .build();
+        assert!(logger.enabled(Level::Debug, "crate1"));
+
+        let logger = LogBuilder::new(slog::Discard)
+                        .filter(None, FilterLevel::Error)
+                        .filter(Some(""), FilterLevel::Debug)
+                        .build();
+        assert!(logger.enabled(Level::Debug, "crate1"));
+    }
+
+    #[test]
+    fn zero_level() {
+        let logger = make_logger(vec![
+            LogDirective { name: None, level: FilterLevel::Info, levels: None, matcher: None, priority: 0 },
+            LogDirective { name: Some("crate1::mod1".to_string()), level: FilterLevel::Off, levels: None, matcher: None, priority: 0 }
+        ]);
+        assert!(!logger.enabled(Level::Error, "crate1::mod1"));
+        assert!(logger.enabled(Level::Info, "crate2::mod2"));
+    }
+
+    #[test]
+    fn parse_logging_spec_valid() {
+        let (dirs, filter) = parse_logging_spec("crate1::mod1=error,crate1::mod2,crate2=debug");
+        assert_eq!(dirs.len(), 3);
+        assert_eq!(dirs[0].name, Some("crate1::mod1".to_string()));
+        assert_eq!(dirs[0].level, FilterLevel::Error);
+
+        assert_eq!(dirs[1].name, Some("crate1::mod2".to_string()));
+        assert_eq!(dirs[1].level, FilterLevel::max());
+
+        assert_eq!(dirs[2].name, Some("crate2".to_string()));
+        assert_eq!(dirs[2].level, FilterLevel::Debug);
+        assert!(filter.is_none());
+    }
+
+    #[test]
+    fn parse_logging_spec_invalid_crate() {
+        // test parse_logging_spec with multiple = in specification
+        let (dirs, filter) = parse_logging_spec("crate1::mod1=warn=info,crate2=debug");
+        assert_eq!(dirs.len(), 1);
+        assert_eq!(dirs[0].name, Some("crate2".to_string()));
+        assert_eq!(dirs[0].level, FilterLevel::Debug);
+        assert!(filter.is_none());
+    }
+
+    #[test]
+    fn parse_logging_spec_invalid_log_level() {
+        // test parse_logging_spec with 'noNumber' as log level
+        let (dirs, filter) = parse_logging_spec("crate1::mod1=noNumber,crate2=debug");
+        assert_eq!(dirs.len(), 1);
+        assert_eq!(dirs[0].name, Some("crate2".to_string()));
+        assert_eq!(dirs[0].level, FilterLevel::Debug);
+        assert!(filter.is_none());
+    }
+
+    #[test]
+    fn parse_logging_spec_string_log_level() {
+        // test parse_logging_spec with 'warn' as log level
+        let (dirs, filter) = parse_logging_spec("crate1::mod1=wrong,crate2=warn");
+        assert_eq!(dirs.len(), 1);
+        assert_eq!(dirs[0].name, Some("crate2".to_string()));
+        assert_eq!(dirs[0].level, FilterLevel::Warning);
+        assert!(filter.is_none());
+    }
+
+    #[test]
+    fn parse_logging_spec_empty_log_level() {
+        // test parse_logging_spec with '' as log level
+        let (dirs, filter) = parse_logging_spec("crate1::mod1=wrong,crate2=");
+        assert_eq!(dirs.len(), 1);
+        assert_eq!(dirs[0].name, Some("crate2".to_string()));
+        assert_eq!(dirs[0].level, FilterLevel::max());
+        assert!(filter.is_none());
+    }
+
+    #[test]
+    fn parse_logging_spec_global() {
+        // test parse_logging_spec with no crate
+        let (dirs, filter) = parse_logging_spec("warn,crate2=debug");
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(dirs[0].name, None);
+        assert_eq!(dirs[0].level, FilterLevel::Warning);
+        assert_eq!(dirs[1].name, Some("crate2".to_string()));
+        assert_eq!(dirs[1].level, FilterLevel::Debug);
+        assert!(filter.is_none());
+    }
+
+    #[test]
+    fn parse_spec_reports_errors() {
+        let parsed = parse_spec("crate1::mod1=warn=info,crate2=nope,crate3=debug/abc");
+        assert_eq!(parsed.directives.len(), 1);
+        assert_eq!(parsed.directives[0].name(), Some("crate3"));
+        assert_eq!(parsed.filter, Some("abc".to_string()));
+        assert_eq!(parsed.errors, vec![
+            ParseError::InvalidDirective("crate1::mod1=warn=info".to_string()),
+            ParseError::InvalidLevel("nope".to_string()),
+        ]);
+
+        let parsed = parse_spec("crate1/a/b");
+        assert_eq!(parsed.directives, vec![LogDirective::new(Some("crate1"), FilterLevel::max())]);
+        assert_eq!(parsed.filter, Some("a/b".to_string()));
+        assert!(parsed.errors.is_empty());
+    }
+
+    #[test]
+    fn parse_spec_options() {
+        let parsed = parse_spec("info,net=debug/a;b;color=never;sample=10");
+        assert_eq!(parsed.directives.len(), 2);
+        assert_eq!(parsed.filter, Some("a;b".to_string()));
+        assert_eq!(parsed.options.color, Some(Color::Never));
+        assert_eq!(parsed.options.sample, Some(10));
+        assert!(!parsed.options.filter_disable);
+        assert!(parsed.errors.is_empty());
+
+        let parsed = parse_spec("info/abc;filter-disable=true;sample=0");
+        assert_eq!(parsed.filter, Some("abc".to_string()));
+        assert!(parsed.options.filter_disable);
+        assert_eq!(parsed.options.sample, None);
+        assert_eq!(parsed.errors, vec![ParseError::InvalidOption("sample=0".to_string())]);
+
+        let logger = LogBuilder::new(slog::Discard).parse("info/abc;filter-disable=true").build();
+        assert!(logger.filter.is_none());
+    }
+
+    #[test]
+    fn sample_option() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info;sample=3").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        for i in 0..7 {
+            slog::info!(log, "{}", i);
+        }
+        assert_eq!(capture.messages(), vec!["0", "3", "6"]);
+    }
+
+    #[test]
+    fn profiles() {
+        let builder = || {
+            LogBuilder::new(slog::Discard)
+                .profile("debugging", "debug,hyper=warn")
+                .profile("db", "warn,app::db=trace/^query")
+                .profile("nested", "@profile:debugging")
+        };
+
+        let logger = builder().parse("@profile:debugging").build();
+        assert!(logger.enabled(Level::Debug, "app"));
+        assert!(!logger.enabled(Level::Info, "hyper"));
+
+        // The rest of the spec overrides the profile
+        let logger = builder().parse("hyper=info,@profile:debugging").build();
+        assert!(logger.enabled(Level::Info, "hyper"));
+        assert!(logger.enabled(Level::Debug, "app"));
+
+        let logger = builder().parse("@profile:db").build();
+        assert!(logger.enabled(Level::Trace, "app::db::pool"));
+        assert!(!logger.enabled(Level::Info, "app"));
+        assert_eq!(logger.filter_source(), Some("^query"));
+        let logger = builder().parse("@profile:db/^select").build();
+        assert_eq!(logger.filter_source(), Some("^select"));
+
+        let (_, report) = builder().parse_report("info,@profile:missing,@profile:nested");
+        assert_eq!(report.accepted.len(), 1);
+        assert_eq!(report.rejected, vec![
+            ParseError::UnknownProfile("missing".to_string()),
+            ParseError::InvalidDirective("@profile:debugging".to_string()),
+        ]);
+        assert_eq!(report.rejected[0].to_string(), "unknown logging profile 'missing', ignoring it");
+    }
+
+    #[test]
+    fn parse_report() {
+        let (builder, report) = LogBuilder::new(slog::Discard)
+                                    .parse_report("info,bad=nope,crate2=debug");
+        assert_eq!(report.dropped(), 1);
+        assert_eq!(report.rejected, vec![ParseError::InvalidLevel("nope".to_string())]);
+        assert_eq!(report.accepted.len(), 2);
+        assert_eq!(report.accepted[0].name(), None);
+        assert_eq!(report.accepted[1].name(), Some("crate2"));
+
+        let logger = builder.build();
+        assert!(logger.enabled(Level::Debug, "crate2"));
+        assert!(!logger.enabled(Level::Debug, "bad"));
+    }
+
+    #[test]
+    fn filter_bound() {
+        let parsed = parse_spec("info/foo@>=info");
+        assert_eq!(parsed.filter, Some("foo".to_string()));
+        assert_eq!(parsed.filter_bound, Some(FilterBound::AtLeast(Level::Info)));
+
+        let parsed = parse_spec("info/user@example");
+        assert_eq!(parsed.filter, Some("user@example".to_string()));
+        assert_eq!(parsed.filter_bound, None);
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("debug/foo@<info").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::error!(log, "error bar");
+        slog::info!(log, "info bar");
+        slog::debug!(log, "debug bar");
+        slog::debug!(log, "debug foo");
+        assert_eq!(capture.messages(), vec!["error bar", "info bar", "debug foo"]);
+    }
+
+    #[test]
+    fn comments() {
+        let logger = LogBuilder::new(slog::Discard).allow_comments().parse("info # default level").build();
+        assert!(logger.enabled(Level::Info, "crate1"));
+        assert!(!logger.enabled(Level::Debug, "crate1"));
+
+        let (_, report) = LogBuilder::new(slog::Discard)
+                            .allow_comments()
+                            .parse_report("# defaults\nwarn\n\ncrate1=debug # noisy\n/foo # only foo");
+        assert!(report.rejected.is_empty());
+        assert_eq!(report.accepted.len(), 2);
+
+        let logger = LogBuilder::new(slog::Discard).parse("info/a#b").build();
+        assert_eq!(logger.filter.unwrap().to_string(), "a#b");
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn parse_toml() {
+        let config: toml::value::Table = toml::from_str(r#"
+            [logging]
+            level = "warn"
+            filter = "foo"
+
+            [logging.modules]
+            "crate1" = "debug"
+            "crate1::mod1" = "error"
+        "#).unwrap();
+        let table = config["logging"].as_table().unwrap();
+        let logger = LogBuilder::new(slog::Discard).parse_toml(table).unwrap().build();
+        assert!(logger.would_log(Level::Warning, "crate2"));
+        assert!(!logger.would_log(Level::Info, "crate2"));
+        assert!(logger.would_log(Level::Debug, "crate1"));
+        assert!(!logger.would_log(Level::Warning, "crate1::mod1"));
+        assert_eq!(logger.filter.unwrap().to_string(), "foo");
+
+        let config: toml::value::Table = toml::from_str(r#"
+            level = "info"
+            modules = { crate1 = "nope" }
+        "#).unwrap();
+        let err = LogBuilder::new(slog::Discard).parse_toml(&config).err().unwrap();
+        assert_eq!(err, ParseError::InvalidLevel("nope".to_string()));
+        let config: toml::value::Table = toml::from_str("level = 3").unwrap();
+        let err = LogBuilder::new(slog::Discard).parse_toml(&config).err().unwrap();
+        assert_eq!(err, ParseError::InvalidOption("level=3".to_string()));
+
+        // Keys and values aren't spec syntax
+        let config: toml::value::Table = toml::from_str(r#"
+            level = "info"
+            modules = { "a/b" = "debug" }
+        "#).unwrap();
+        let err = LogBuilder::new(slog::Discard).parse_toml(&config).err().unwrap();
+        assert_eq!(err, ParseError::InvalidDirective("a/b".to_string()));
+        let config: toml::value::Table = toml::from_str(r#"
+            modules = { crate1 = "debug,crate2=trace" }
+        "#).unwrap();
+        let err = LogBuilder::new(slog::Discard).parse_toml(&config).err().unwrap();
+        assert_eq!(err, ParseError::InvalidLevel("debug,crate2=trace".to_string()));
+        let config: toml::value::Table = toml::from_str(r#"
+            level = "info"
+            filter = "a;sample=2"
+        "#).unwrap();
+        let logger = LogBuilder::new(slog::Discard).parse_toml(&config).unwrap().build();
+        assert_eq!(logger.filter_source(), Some("a;sample=2"));
+        assert_eq!(logger.sample, None);
+    }
+
+    #[test]
+    fn max_depth() {
+        let logger = LogBuilder::new(slog::Discard)
+            .parse("info,crate2::mod1=debug")
+            .max_depth(2)
+            .build();
+        assert!(logger.would_log(Level::Info, "crate1"));
+        assert!(logger.would_log(Level::Info, "crate1::mod1"));
+        assert!(!logger.would_log(Level::Error, "crate1::mod1::sub"));
+        assert!(!logger.would_log(Level::Error, "crate1::mod1::sub::gen"));
+        assert!(logger.would_log(Level::Debug, "crate2::mod1::sub"));
+        assert!(logger.would_log(Level::Debug, "crate2::mod1::sub::gen"));
+        assert!(!logger.would_log(Level::Info, "crate2::mod2::sub"));
+    }
+
+    #[test]
+    fn min_floor() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("off").min_floor(Level::Warning).build();
+        assert!(logger.would_log(Level::Error, "crate1"));
+        assert!(!logger.would_log(Level::Info, "crate1"));
+        assert_eq!(logger.filter(), FilterLevel::Warning);
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::error!(log, "error");
+        slog::warn!(log, "warn");
+        slog::info!(log, "info");
+        assert_eq!(capture.messages(), vec!["error", "warn"]);
+
+        env::set_var("SLOG_ENVLOGGER_TEST_FLOOR", "error");
+        let env = Env::new().filter("SLOG_ENVLOGGER_TEST_UNSET_SPEC").floor("SLOG_ENVLOGGER_TEST_FLOOR")
+            .default_filter_or("off");
+        let logger = EnvLogger::from_env(slog::Discard, env);
+        assert!(logger.would_log(Level::Error, "crate1"));
+        assert!(!logger.would_log(Level::Warning, "crate1"));
+    }
+
+    #[test]
+    fn allowlist() {
+        let logger = LogBuilder::new(slog::Discard).parse("trace,crate3=error")
+            .allowlist(&["crate1", "crate2::api"], FilterLevel::Info)
+            .min_floor(Level::Critical)
+            .build();
+        assert!(logger.enabled(Level::Info, "crate1::db"));
+        assert!(!logger.enabled(Level::Debug, "crate1::db"));
+        assert!(logger.enabled(Level::Info, "crate2::api"));
+        assert!(!logger.enabled(Level::Critical, "crate2"));
+        assert!(!logger.enabled(Level::Critical, "crate3"));
+        assert!(!logger.enabled(Level::Error, "crate4"));
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("@user=admin=trace")
+            .allowlist(&["slog_envlogger"], FilterLevel::Warning)
+            .build();
+        let log = slog::Logger::root(logger, slog::o!("user" => "admin"));
+        slog::info!(log, "listed");
+        assert_eq!(capture.messages(), vec!["listed"]);
+        let logger = LogBuilder::new(capture.clone()).parse("@user=admin=trace")
+            .allowlist(&["crate1"], FilterLevel::Warning)
+            .build();
+        let log = slog::Logger::root(logger, slog::o!("user" => "admin"));
+        slog::error!(log, "unlisted");
+        assert_eq!(capture.messages(), vec!["listed"]);
+    }
+
+    #[test]
+    fn many_globs() {
+        use super::{GlobMatcher, SuffixMatcher};
+
+        let logger = (0..10)
+            .fold(LogBuilder::new(slog::Discard).parse("warn,crate1::db=error"), |builder, i| {
+                builder.filter_matcher(GlobMatcher(format!("*::sub{}::*", i)), FilterLevel::Info)
+            })
+            .filter_matcher(GlobMatcher("*::db".to_string()), FilterLevel::Debug)
+            .filter_matcher(SuffixMatcher("::db".to_string()), FilterLevel::Trace)
+            .filter_matcher(GlobMatcher("crate?::[x]".to_string()), FilterLevel::Debug)
+            .filter_matcher(GlobMatcher("crate2::*".to_string()), FilterLevel::Error)
+            .build();
+        #[cfg(feature = "globset")]
+        assert!(logger.globs.is_some());
+
+        assert!(logger.enabled(Level::Info, "crate1::sub3::a"));
+        assert!(!logger.enabled(Level::Debug, "crate1::sub3::a"));
+        assert!(!logger.enabled(Level::Info, "crate1::sub3"));
+        assert!(logger.enabled(Level::Trace, "crate1::db"));
+        assert!(logger.enabled(Level::Debug, "crate1::[x]"));
+        assert!(!logger.enabled(Level::Debug, "crate1::x"));
+        // Matchers take precedence over names, and the most verbose one wins
+        assert!(logger.enabled(Level::Info, "crate2::sub9::a"));
+        assert!(!logger.enabled(Level::Warning, "crate2::a"));
+        assert!(logger.enabled(Level::Warning, "crate3"));
+        assert!(logger.enabled(Level::Warning, ""));
+    }
+
+    #[test]
+    fn matcher_ties() {
+        use super::{GlobMatcher, SuffixMatcher};
+
+        let logger = LogBuilder::new(slog::Discard)
+            .filter_matcher(GlobMatcher("*::db".to_string()), FilterLevel::Debug)
+            .filter_matcher(SuffixMatcher("::db".to_string()), FilterLevel::Warning)
+            .build();
+        assert!(logger.enabled(Level::Debug, "crate1::db"));
+        assert!(!logger.enabled(Level::Trace, "crate1::db"));
+
+        let logger = LogBuilder::new(slog::Discard)
+            .filter_matcher(GlobMatcher("*::db".to_string()), FilterLevel::Debug)
+            .filter_matcher(SuffixMatcher("::db".to_string()), FilterLevel::Warning)
+            .prefer_quieter()
+            .build();
+        assert!(logger.enabled(Level::Warning, "crate1::db"));
+        assert!(!logger.enabled(Level::Info, "crate1::db"));
+
+        // Named directives of the same module aren't ties, the later one wins
+        let logger = LogBuilder::new(slog::Discard).parse("crate1=debug,crate1=warn").build();
+        assert!(!logger.enabled(Level::Info, "crate1"));
+    }
+
+    #[test]
+    fn directive_capacity() {
+        let builder = LogBuilder::with_capacity(slog::Discard, 100);
+        assert!(builder.directives.capacity() >= 100);
+
+        // Growing one by one would end up at a power of two
+        let spec = (0..100).map(|i| format!("crate{}=debug", i)).collect::<Vec<_>>().join(",");
+        let parsed = parse_spec(&spec);
+        assert_eq!(parsed.directives.len(), 100);
+        assert!(parsed.directives.capacity() < 128);
+
+        let builder = LogBuilder::new(slog::Discard).parse(&spec);
+        assert!(builder.directives.capacity() < 128);
+    }
+
+    #[test]
+    fn parse_iter() {
+        let from_file = "warn,crate1=info/foo".to_string();
+        let logger = LogBuilder::new(slog::Discard)
+            .parse_iter(vec![from_file.as_str(), "crate2=debug/bar", "crate1=trace"])
+            .build();
+        assert_eq!(logger.directives, vec![
+            LogDirective::new(None, FilterLevel::Warning),
+            LogDirective::new(Some("crate1"), FilterLevel::Info),
+            LogDirective::new(Some("crate2"), FilterLevel::Debug),
+            LogDirective::new(Some("crate1"), FilterLevel::Trace),
+        ]);
+        assert!(logger.enabled(Level::Trace, "crate1"));
+        assert_eq!(logger.filter_source(), Some("bar"));
+
+        let logger = LogBuilder::new(slog::Discard).parse_iter(&[from_file]).build();
+        assert!(logger.enabled(Level::Info, "crate1"));
+    }
+
+    #[test]
+    fn install_thread_local() {
+        let log = slog::Logger::root(super::ThreadLocalDrain, slog::o!());
+        let run = |spec: &'static str| {
+            let log = log.clone();
+            thread::spawn(move || {
+                let capture = Capture::default();
+                let _guard = LogBuilder::new(capture.clone()).parse(spec).install_thread_local();
+                slog::info!(log, "info");
+                slog::warn!(log, "warning");
+                capture.messages()
+            })
+        };
+        let (info, warn) = (run("info"), run("warn"));
+        assert_eq!(info.join().unwrap(), vec!["info", "warning"]);
+        assert_eq!(warn.join().unwrap(), vec!["warning"]);
+
+        // Restored when the guard is dropped
+        let outer = Capture::default();
+        let _outer = LogBuilder::new(outer.clone()).parse("info").install_thread_local();
+        {
+            let inner = Capture::default();
+            let _inner = LogBuilder::new(inner.clone()).parse("error").install_thread_local();
+            slog::info!(log, "inner");
+            assert!(inner.messages().is_empty());
+        }
+        slog::info!(log, "outer");
+        assert_eq!(outer.messages(), vec!["outer"]);
+    }
+
+    #[test]
+    fn thread_guards_out_of_order() {
+        let log = slog::Logger::root(super::ThreadLocalDrain, slog::o!());
+        let first = Capture::default();
+        let second = Capture::default();
+        let first_guard = LogBuilder::new(first.clone()).parse("info").install_thread_local();
+        let second_guard = LogBuilder::new(second.clone()).parse("info").install_thread_local();
+        drop(first_guard);
+        slog::info!(log, "second");
+        drop(second_guard);
+        slog::info!(log, "none");
+        assert!(first.messages().is_empty());
+        assert_eq!(second.messages(), vec!["second"]);
+
+        // Drains may install loggers while logging
+        struct Reinstall(Capture);
+
+        impl slog::Drain for Reinstall {
+            type Ok = ();
+            type Err = slog::Never;
+
+            fn log(&self, info: &slog::Record, _: &slog::OwnedKVList) -> Result<(), slog::Never> {
+                let _guard = LogBuilder::new(slog::Discard).install_thread_local();
+                self.0.0.lock().unwrap().push(info.msg().to_string());
+                Ok(())
+            }
+        }
+
+        let capture = Capture::default();
+        let _guard = LogBuilder::new(Reinstall(capture.clone())).parse("info").install_thread_local();
+        slog::info!(log, "reentrant");
+        assert_eq!(capture.messages(), vec!["reentrant"]);
+    }
+
+    #[test]
+    fn shadow_config() {
+        mod db {
+            pub fn log(log: &slog::Logger) {
+                slog::debug!(log, "query");
+                slog::info!(log, "connected");
+            }
+        }
+
+        let capture = Capture::default();
+        let logger = Arc::new(LogBuilder::new(capture.clone()).parse("info").build());
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+        logger.set_shadow_config(&format!("warn,{}::db=debug", module_path!()));
+        for _ in 0..3 {
+            db::log(&log);
+            slog::info!(log, "request");
+            slog::warn!(log, "slow");
+        }
+        // The debug records would be logged, the info ones outside of `db` not
+        assert_eq!(logger.shadow_diff_count(), 6);
+        assert_eq!(capture.messages().len(), 9);
+
+        logger.set_shadow_config("info");
+        db::log(&log);
+        assert_eq!(logger.shadow_diff_count(), 0);
+
+        logger.set_shadow_config("debug");
+        logger.set_shadow_config("");
+        slog::debug!(log, "debug");
+        assert_eq!(logger.shadow_diff_count(), 0);
+    }
+
+    #[test]
+    fn enable_for_count() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info")
+            .enable_for_count(Some(module_path!()), FilterLevel::Debug, 3)
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        for i in 0..8 {
+            // Enabled anyway, so not counted
+            slog::info!(log, "info {}", i);
+            slog::debug!(log, "debug {}", i);
+        }
+        let messages = capture.messages();
+        let debug = messages.iter().filter(|m| m.starts_with("debug")).collect::<Vec<_>>();
+        assert_eq!(debug, vec!["debug 0", "debug 1", "debug 2"]);
+        assert_eq!(messages.len(), 11);
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<EnvLogger<slog::Discard>>();
+        assert_send_sync::<EnvLogger<Capture>>();
+        assert_send_sync::<LogBuilder<slog::Discard>>();
+
+        let capture = Capture::default();
+        let logger = Arc::new(LogBuilder::new(capture.clone()).parse("info").sample(1).build());
+        let threads = (0..4).map(|i| {
+            let logger = logger.clone();
+            std::thread::spawn(move || {
+                let log = slog::Logger::root(logger, slog::o!());
+                slog::info!(log, "thread {}", i);
+            })
+        }).collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(capture.messages().len(), 4);
+    }
+
+    #[test]
+    fn fork() {
+        use std::sync::atomic::AtomicBool;
+
+        let capture = Capture::default();
+        let parent = LogBuilder::new(capture.clone()).parse("info,crate1=debug,crate2=warn/foo").build();
+        let fork = parent.fork("crate1=warn,crate3=trace");
+        assert!(parent.enabled(Level::Debug, "crate1"));
+        assert!(!fork.enabled(Level::Info, "crate1"));
+        assert!(!parent.enabled(Level::Debug, "crate3"));
+        assert!(fork.enabled(Level::Trace, "crate3"));
+        // Inherited
+        assert!(fork.enabled(Level::Info, "crate4"));
+        assert!(!fork.enabled(Level::Info, "crate2"));
+        assert_eq!(fork.filter_source(), Some("foo"));
+
+        let log = slog::Logger::root(fork, slog::o!());
+        slog::info!(log, "foo");
+        slog::info!(log, "bar");
+        assert_eq!(capture.messages(), vec!["foo"]);
+
+        let fork = parent.fork("/bar");
+        assert_eq!(fork.filter_source(), Some("bar"));
+        assert!(fork.enabled(Level::Debug, "crate1"));
+
+        // The default filter stays when `extra_spec` replaces the filter
+        let capture = Capture::default();
+        let parent = LogBuilder::new(capture.clone()).default_filter("foo").parse("info").build();
+        let log = slog::Logger::root(parent.fork("/@len<100"), slog::o!());
+        slog::info!(log, "foo");
+        slog::info!(log, "bar");
+        assert_eq!(capture.messages(), vec!["foo"]);
+
+        // So do profiles and comments
+        let parent = LogBuilder::new(slog::Discard).profile("quiet", "warn").allow_comments().parse("info").build();
+        assert!(!parent.fork("@profile:quiet").enabled(Level::Info, "crate1"));
+        assert!(parent.fork("crate1=debug # note").enabled(Level::Debug, "crate1"));
+
+        // Warnings go to the same collector
+        let (builder, warnings) = LogBuilder::new(slog::Discard).collect_warnings();
+        let parent = builder.parse("info").build();
+        let _fork = parent.fork("crate1=bogus");
+        assert_eq!(warnings.lock().unwrap().len(), 1);
+
+        // The enabled flag is shared
+        let flag = Arc::new(AtomicBool::new(true));
+        let capture = Capture::default();
+        let parent = LogBuilder::new(capture.clone()).parse("info").enabled_flag(flag.clone()).build();
+        let log = slog::Logger::root(parent.fork("debug"), slog::o!());
+        flag.store(false, Ordering::Relaxed);
+        slog::info!(log, "disabled");
+        assert!(capture.messages().is_empty());
     }
 
     #[test]
-    fn zero_level() {
-        let logger = make_logger(vec![
-            LogDirective { name: None, level: FilterLevel::Info },
-            LogDirective { name: Some("crate1::mod1".to_string()), level: FilterLevel::Off }
-        ]);
-        assert!(!logger.enabled(Level::Error, "crate1::mod1"));
-        assert!(logger.enabled(Level::Info, "crate2::mod2"));
+    fn parse_reader() {
+        use std::io::{self, Cursor};
+
+        let logger = LogBuilder::new(slog::Discard).parse_reader(Cursor::new("app=debug\n")).unwrap().build();
+        assert_eq!(logger.directives, vec![LogDirective::new(Some("app"), FilterLevel::Debug)]);
+
+        let logger = LogBuilder::new(slog::Discard).parse("info").parse_reader(Cursor::new(" \n")).unwrap().build();
+        assert_eq!(logger.directives, vec![LogDirective::new(None, FilterLevel::Info)]);
+
+        let err = LogBuilder::new(slog::Discard).parse_reader(Cursor::new(b"app=\xff")).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
 
     #[test]
-    fn parse_logging_spec_valid() {
-        let (dirs, filter) = parse_logging_spec("crate1::mod1=error,crate1::mod2,crate2=debug");
-        assert_eq!(dirs.len(), 3);
-        assert_eq!(dirs[0].name, Some("crate1::mod1".to_string()));
-        assert_eq!(dirs[0].level, FilterLevel::Error);
+    fn config_dump() {
+        env::set_var("SLOG_ENVLOGGER_TEST_DUMP_SPEC", "info,crate1=debug,crate2=+1/foo");
+        env::remove_var("SLOG_ENVLOGGER_TEST_DUMP");
+        let env = Env::new().filter("SLOG_ENVLOGGER_TEST_DUMP_SPEC").debug("SLOG_ENVLOGGER_TEST_DUMP");
+        let logger = EnvLogger::from_env(slog::Discard, env.clone());
+        assert_eq!(logger.config_dump(&env), None);
 
-        assert_eq!(dirs[1].name, Some("crate1::mod2".to_string()));
-        assert_eq!(dirs[1].level, FilterLevel::max());
+        env::set_var("SLOG_ENVLOGGER_TEST_DUMP", "0");
+        assert_eq!(logger.config_dump(&env), None);
 
-        assert_eq!(dirs[2].name, Some("crate2".to_string()));
-        assert_eq!(dirs[2].level, FilterLevel::Debug);
-        assert!(filter.is_none());
+        env::set_var("SLOG_ENVLOGGER_TEST_DUMP", "1");
+        assert_eq!(logger.config_dump(&env).unwrap(), "\
+logging config: info,crate1=debug,crate2=debug/foo
+  info (from SLOG_ENVLOGGER_TEST_DUMP_SPEC)
+  crate1=debug (from SLOG_ENVLOGGER_TEST_DUMP_SPEC)
+  crate2=debug (from SLOG_ENVLOGGER_TEST_DUMP_SPEC)
+  filter foo (from SLOG_ENVLOGGER_TEST_DUMP_SPEC)
+");
+
+        let env = Env::new().filter("SLOG_ENVLOGGER_TEST_DUMP_UNSET").debug("SLOG_ENVLOGGER_TEST_DUMP");
+        let logger = EnvLogger::from_env(slog::Discard, env.clone());
+        assert_eq!(logger.config_dump(&env).unwrap(), "\
+logging config: error
+  error (from built-in default)
+");
     }
 
     #[test]
-    fn parse_logging_spec_invalid_crate() {
-        // test parse_logging_spec with multiple = in specification
-        let (dirs, filter) = parse_logging_spec("crate1::mod1=warn=info,crate2=debug");
-        assert_eq!(dirs.len(), 1);
-        assert_eq!(dirs[0].name, Some("crate2".to_string()));
-        assert_eq!(dirs[0].level, FilterLevel::Debug);
-        assert!(filter.is_none());
+    fn env_default_filter_or() {
+        env::remove_var("SLOG_ENVLOGGER_TEST_UNSET");
+        env::set_var("SLOG_ENVLOGGER_TEST_SET", "crate1=debug");
+
+        let unset = Env::new().filter("SLOG_ENVLOGGER_TEST_UNSET");
+        assert_eq!(unset.get_filter(), None);
+        let unset = unset.default_filter_or("warn");
+        assert_eq!(unset.get_filter(), Some("warn".to_string()));
+        let set = Env::from("SLOG_ENVLOGGER_TEST_SET").default_filter_or("warn");
+        assert_eq!(set.get_filter(), Some("crate1=debug".to_string()));
+
+        let logger = EnvLogger::from_env(slog::Discard, unset);
+        assert!(logger.enabled(Level::Warning, "crate1"));
+        assert!(!logger.enabled(Level::Info, "crate1"));
+        let logger = EnvLogger::from_env(slog::Discard, set);
+        assert!(logger.enabled(Level::Debug, "crate1"));
     }
 
     #[test]
-    fn parse_logging_spec_invalid_log_level() {
-        // test parse_logging_spec with 'noNumber' as log level
-        let (dirs, filter) = parse_logging_spec("crate1::mod1=noNumber,crate2=debug");
-        assert_eq!(dirs.len(), 1);
-        assert_eq!(dirs[0].name, Some("crate2".to_string()));
-        assert_eq!(dirs[0].level, FilterLevel::Debug);
-        assert!(filter.is_none());
+    fn parse_envs() {
+        env::set_var("SLOG_ENVLOGGER_TEST_BASE", "info,crate1=warn,crate2=debug/foo");
+        env::set_var("SLOG_ENVLOGGER_TEST_EMPTY", "");
+        env::set_var("SLOG_ENVLOGGER_TEST_EXTRA", "crate1=trace");
+        let logger = LogBuilder::new(slog::Discard)
+                        .parse_envs(&["SLOG_ENVLOGGER_TEST_BASE",
+                                      "SLOG_ENVLOGGER_TEST_UNSET",
+                                      "SLOG_ENVLOGGER_TEST_EMPTY",
+                                      "SLOG_ENVLOGGER_TEST_EXTRA"])
+                        .build();
+        assert!(logger.enabled(Level::Trace, "crate1"));
+        assert!(logger.enabled(Level::Debug, "crate2"));
+        assert!(!logger.enabled(Level::Debug, "crate3"));
+        assert_eq!(logger.filter.unwrap().to_string(), "foo");
     }
 
     #[test]
-    fn parse_logging_spec_string_log_level() {
-        // test parse_logging_spec with 'warn' as log level
-        let (dirs, filter) = parse_logging_spec("crate1::mod1=wrong,crate2=warn");
-        assert_eq!(dirs.len(), 1);
-        assert_eq!(dirs[0].name, Some("crate2".to_string()));
-        assert_eq!(dirs[0].level, FilterLevel::Warning);
-        assert!(filter.is_none());
+    fn validate_spec_reports_all() {
+        assert_eq!(validate_spec("info,crate1=debug/foo;sample=2"), Ok(()));
+        assert_eq!(validate_spec("crate1/a/b"), Ok(()));
+
+        let errors = validate_spec("crate1=nope,crate2=a=b,info/[;sample=x").unwrap_err();
+        let has_regex = cfg!(any(feature = "regex", feature = "regex-lite"));
+        assert_eq!(errors.len(), if has_regex { 4 } else { 3 });
+        assert_eq!(errors[0], ParseError::InvalidOption("sample=x".to_string()));
+        assert_eq!(errors[1], ParseError::InvalidLevel("nope".to_string()));
+        assert_eq!(errors[2], ParseError::InvalidDirective("crate2=a=b".to_string()));
+        #[cfg(any(feature = "regex", feature = "regex-lite"))]
+        match errors[3] {
+            ParseError::InvalidFilter(_) => {},
+            ref e => panic!("unexpected error: {:?}", e),
+        }
     }
 
     #[test]
-    fn parse_logging_spec_empty_log_level() {
-        // test parse_logging_spec with '' as log level
-        let (dirs, filter) = parse_logging_spec("crate1::mod1=wrong,crate2=");
-        assert_eq!(dirs.len(), 1);
-        assert_eq!(dirs[0].name, Some("crate2".to_string()));
-        assert_eq!(dirs[0].level, FilterLevel::max());
-        assert!(filter.is_none());
+    #[cfg(feature = "regex")]
+    fn parse_error_source() {
+        extern crate regex;
+        use std::error::Error;
+
+        let pattern = String::from("[");
+        let errors = validate_spec(&format!("info/{}", pattern)).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let expected = regex::Regex::new(&pattern).unwrap_err();
+        assert_eq!(errors[0].to_string(), format!("invalid regex filter - {}", expected));
+        let source = errors[0].source().expect("regex error as source");
+        assert_eq!(source.downcast_ref::<regex::Error>(), Some(&expected));
+
+        assert_eq!(ParseError::InvalidLevel("nope".to_string()).to_string(),
+                   "invalid logging spec 'nope', ignoring it");
+        assert!(ParseError::InvalidLevel("nope".to_string()).source().is_none());
     }
 
     #[test]
-    fn parse_logging_spec_global() {
-        // test parse_logging_spec with no crate
-        let (dirs, filter) = parse_logging_spec("warn,crate2=debug");
-        assert_eq!(dirs.len(), 2);
+    fn parse_logging_spec_wildcard() {
+        let (dirs, _) = parse_logging_spec("*=debug,crate1=warn");
+        let (expected, _) = parse_logging_spec("debug,crate1=warn");
+        assert_eq!(dirs, expected);
         assert_eq!(dirs[0].name, None);
-        assert_eq!(dirs[0].level, FilterLevel::Warning);
-        assert_eq!(dirs[1].name, Some("crate2".to_string()));
-        assert_eq!(dirs[1].level, FilterLevel::Debug);
-        assert!(filter.is_none());
+        assert_eq!(dirs[0].level, FilterLevel::Debug);
+
+        let (dirs, _) = parse_logging_spec("*");
+        assert_eq!(dirs[0].name, None);
+        assert_eq!(dirs[0].level, FilterLevel::max());
+
+        let logger = LogBuilder::new(slog::Discard).parse("*=debug").build();
+        assert!(logger.enabled(Level::Debug, "crate1"));
+        assert!(!logger.enabled(Level::Trace, "crate1"));
+    }
+
+    #[test]
+    fn parse_filter_level() {
+        use super::parse_filter_level;
+
+        let spellings: &[(&[&str], FilterLevel)] = &[
+            (&["off", "OFF", "o", "0"], FilterLevel::Off),
+            (&["critical", "crit", "c", "Critical", "1"], FilterLevel::Critical),
+            (&["error", "erro", "err", "e", "ERROR", "2"], FilterLevel::Error),
+            (&["warning", "warn", "wa", "w", "Warn", "3"], FilterLevel::Warning),
+            (&["info", "inf", "i", "INFO", "4"], FilterLevel::Info),
+            (&["debug", "debg", "deb", "d", "DeBuG", "5"], FilterLevel::Debug),
+            (&["trace", "trce", "tr", "t", "TRACE", "6", " trace\n"], FilterLevel::Trace),
+        ];
+        for &(spellings, level) in spellings {
+            for spelling in spellings {
+                assert_eq!(parse_filter_level(spelling), Some(level), "{:?}", spelling);
+            }
+        }
+        for invalid in &["", "7", "-1", "+1", "warnings", "debugs", "x", "in fo"] {
+            assert_eq!(parse_filter_level(invalid), None, "{:?}", invalid);
+        }
+
+        // Same as in specs
+        let (dirs, _) = parse_logging_spec("crate1=3,crate2=deb,@id=1=t");
+        assert_eq!(dirs[0].level(), FilterLevel::Warning);
+        assert_eq!(dirs[1].level(), FilterLevel::Debug);
+    }
+
+    #[test]
+    fn parse_logging_spec_shorthand() {
+        let (dirs, _) = parse_logging_spec("d:app,w:net::tcp,info:db,trace|info:io");
+        let (expected, _) = parse_logging_spec("app=debug,net::tcp=warn,db=info,io=trace|info");
+        assert_eq!(dirs, expected);
+
+        // Module paths are left alone
+        let (dirs, _) = parse_logging_spec("app::mod=debug,d::app");
+        assert_eq!(dirs[0].name(), Some("app::mod"));
+        assert_eq!(dirs[0].level(), FilterLevel::Debug);
+        assert_eq!(dirs[1].name(), Some("d::app"));
+        assert_eq!(dirs[1].level(), FilterLevel::max());
+
+        // Not a level, so a (weird) module name
+        let (dirs, _) = parse_logging_spec("x:app");
+        assert_eq!(dirs[0].name(), Some("x:app"));
+    }
+
+    #[test]
+    fn parse_logging_spec_off() {
+        for spec in &["off", "OFF", "0", "none", "None", " none "] {
+            let (dirs, filter) = parse_logging_spec(spec);
+            assert_eq!(dirs, vec![LogDirective { name: None, level: FilterLevel::Off, levels: None, matcher: None, priority: 0 }]);
+            assert!(filter.is_none());
+
+            let logger = LogBuilder::new(slog::Discard).parse(spec).build();
+            for level in &[Level::Critical, Level::Error, Level::Info, Level::Trace] {
+                assert!(!logger.enabled(*level, "crate1"));
+                assert!(!logger.enabled(*level, ""));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "init")]
+    fn init_logs_spec_warnings() {
+        env::set_var("SLOG_ENVLOGGER_TEST_INIT_SPEC", "warn,crate1=bogus");
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let log = super::init::root_logger(Capture(messages.clone()),
+                                           Env::new().filter("SLOG_ENVLOGGER_TEST_INIT_SPEC"));
+        slog::warn!(log, "after");
+        assert_eq!(Capture(messages).messages(),
+                   vec!["invalid logging spec 'bogus', ignoring it", "after"]);
+
+        // Logged as warnings, so subject to the spec
+        env::set_var("SLOG_ENVLOGGER_TEST_INIT_SPEC", "error,crate1=bogus");
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let _log = super::init::root_logger(Capture(messages.clone()),
+                                            Env::new().filter("SLOG_ENVLOGGER_TEST_INIT_SPEC"));
+        assert!(Capture(messages).messages().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "init")]
+    fn init_from() {
+        env::set_var("SLOG_ENVLOGGER_TEST_INIT_FROM", "warn");
+        let env = Env::only("SLOG_ENVLOGGER_TEST_INIT_FROM");
+        assert_eq!(env.get_floor(), None);
+        assert!(!env.get_debug());
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let log = super::init::root_logger(Capture(messages.clone()), env);
+        slog::info!(log, "info");
+        slog::warn!(log, "warning");
+        assert_eq!(Capture(messages).messages(), vec!["warning"]);
+    }
+
+    #[test]
+    #[cfg(feature = "init")]
+    fn level_colors() {
+        use std::io;
+        use slog::Drain;
+        use super::init::LevelColors;
+        extern crate slog_term;
+
+        #[derive(Clone, Default)]
+        struct Output(Arc<Mutex<Vec<u8>>>);
+
+        impl io::Write for Output {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Output::default();
+        let decorator = slog_term::PlainSyncDecorator::new(output.clone());
+        let decorator = LevelColors::new(decorator, vec![(Level::Error, 9)], true);
+        let drain = Mutex::new(slog_term::CompactFormat::new(decorator).build()).fuse();
+        let log = slog::Logger::root(drain, slog::o!());
+        slog::error!(log, "red");
+        slog::warn!(log, "plain");
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("\x1b[91mERRO\x1b[0m red"), "{:?}", output);
+        assert!(output.contains("WARN plain"), "{:?}", output);
+
+        let output = Output::default();
+        let decorator = slog_term::PlainSyncDecorator::new(output.clone());
+        let decorator = LevelColors::new(decorator, vec![(Level::Error, 9)], false);
+        let drain = Mutex::new(slog_term::CompactFormat::new(decorator).build()).fuse();
+        let log = slog::Logger::root(drain, slog::o!());
+        slog::error!(log, "plain");
+        assert!(!output.0.lock().unwrap().contains(&0x1b));
+    }
+
+    #[test]
+    fn strip_ansi_for_filter() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/error: disk").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "\x1b[1;31merror\x1b[0m: disk full");
+        assert!(capture.messages().is_empty());
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/error: disk@len<20")
+            .strip_ansi_for_filter()
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "\x1b[1;31merror\x1b[0m: disk full");
+        slog::info!(log, "\x1b]0;title\x07error: disk full");
+        slog::info!(log, "\x1b[31merror: disk is very full");
+        assert_eq!(capture.messages(), vec![
+            "\x1b[1;31merror\x1b[0m: disk full",
+            "\x1b]0;title\x07error: disk full",
+        ]);
+    }
+
+    #[test]
+    fn stop_after_first_error() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info").stop_after_first_error().build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "starting");
+        slog::warn!(log, "warning");
+        slog::error!(log, "failed");
+        slog::info!(log, "after");
+        slog::crit!(log, "critical after");
+        assert_eq!(capture.messages(), vec!["starting", "warning", "failed"]);
+
+        // Errors that are filtered out don't count
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/keep").stop_after_first_error().build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::error!(log, "dropped");
+        slog::info!(log, "keep going");
+        assert_eq!(capture.messages(), vec!["keep going"]);
+    }
+
+    #[test]
+    fn enabled_flag() {
+        use std::sync::atomic::AtomicBool;
+
+        let flag = Arc::new(AtomicBool::new(true));
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info").enabled_flag(flag.clone()).build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "on");
+        flag.store(false, Ordering::Relaxed);
+        slog::error!(log, "off");
+        flag.store(true, Ordering::Relaxed);
+        slog::info!(log, "on again");
+        assert_eq!(capture.messages(), vec!["on", "on again"]);
+    }
+
+    #[test]
+    fn with_silenced() {
+        let capture = Capture::default();
+        let logger = Arc::new(LogBuilder::new(capture.clone()).parse("info").build());
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+        slog::info!(log, "before");
+        let n = logger.with_silenced(|| {
+            slog::info!(log, "silenced");
+            logger.with_silenced(|| slog::error!(log, "nested"));
+            slog::info!(log, "still silenced");
+            1
+        });
+        assert_eq!(n, 1);
+        slog::info!(log, "after");
+
+        let result = std::panic::catch_unwind(|| {
+            logger.with_silenced(|| panic!("noisy library"))
+        });
+        assert!(result.is_err());
+        slog::info!(log, "after panic");
+
+        let other = thread::spawn({
+            let log = log.clone();
+            move || slog::info!(log, "other thread")
+        });
+        logger.with_silenced(|| other.join().unwrap());
+        assert_eq!(capture.messages(), vec!["before", "after", "after panic", "other thread"]);
+    }
+
+    #[test]
+    fn map_message() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/login")
+            .map_message(|msg| match msg.find("password=") {
+                Some(i) => format!("{}password=***", &msg[..i]).into(),
+                None => msg.into(),
+            })
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "login user=bob password={}", "hunter2");
+        slog::info!(log, "login user=bob");
+        slog::info!(log, "logout password=hunter2");
+        assert_eq!(capture.messages(), vec!["login user=bob password=***", "login user=bob"]);
+    }
+
+    #[test]
+    fn custom_message_buf() {
+        use std::cell::Cell;
+        use std::fmt;
+        use super::MessageBuf;
+
+        thread_local! {
+            static HANDED_OUT: Cell<usize> = const { Cell::new(0) };
+        }
+
+        struct CountingBuf(String);
+
+        impl fmt::Write for CountingBuf {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.0.write_str(s)
+            }
+        }
+
+        impl MessageBuf for CountingBuf {
+            fn with_buf<F: FnOnce(&mut Self) -> R, R>(f: F) -> R {
+                HANDED_OUT.with(|n| n.set(n.get() + 1));
+                f(&mut CountingBuf(String::with_capacity(64)))
+            }
+
+            fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            fn retain<F: FnMut(char) -> bool>(&mut self, f: F) {
+                self.0.retain(f)
+            }
+        }
+
+        let capture = Capture::default();
+        let logger: EnvLogger<Capture, CountingBuf> = LogBuilder::new(capture.clone())
+                        .parse("info/keep@len<10")
+                        .strip_ansi_for_filter()
+                        .build_with_buf();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "keep it");
+        slog::info!(log, "\x1b[1mkeep\x1b[0m");
+        slog::info!(log, "drop");
+        slog::info!(log, "keep it going");
+        slog::debug!(log, "keep");
+        assert_eq!(capture.messages(), vec!["keep it", "\x1b[1mkeep\x1b[0m"]);
+        assert_eq!(HANDED_OUT.with(|n| n.get()), 4);
+    }
+
+    #[test]
+    fn max_filter_len() {
+        let dump = format!("dump {}", "x".repeat(100));
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/keep").max_filter_len(20).build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "keep short");
+        slog::info!(log, "drop short");
+        slog::info!(log, "{}", dump);
+        slog::debug!(log, "{}", dump);
+        assert_eq!(capture.messages(), vec!["keep short".to_string(), dump.clone()]);
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone())
+                        .parse("info/dump")
+                        .max_filter_len(20)
+                        .oversized_policy(OversizedPolicy::Drop)
+                        .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "dump short");
+        slog::info!(log, "{}", dump);
+        assert_eq!(capture.messages(), vec!["dump short"]);
+    }
+
+    #[test]
+    fn require_prefix() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info").require_prefix("[DEBUG-ME]").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "[DEBUG-ME] cache miss for {}", 42);
+        slog::info!(log, "cache miss for {}", 42);
+        slog::info!(log, "cache miss [DEBUG-ME]");
+        slog::debug!(log, "[DEBUG-ME] too verbose");
+        assert_eq!(capture.messages(), vec!["[DEBUG-ME] cache miss for 42"]);
+
+        // Both the prefix and the filter have to match
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/db").require_prefix("[DEBUG-ME]").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "[DEBUG-ME] db timeout");
+        slog::info!(log, "[DEBUG-ME] cache timeout");
+        slog::info!(log, "db timeout");
+        assert_eq!(capture.messages(), vec!["[DEBUG-ME] db timeout"]);
+
+        // Escape sequences are stripped with the module as filter target too
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/tests")
+            .filter_target(super::FilterTarget::Module)
+            .strip_ansi_for_filter()
+            .require_prefix("[DEBUG-ME]")
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "\x1b[33m[DEBUG-ME]\x1b[0m cache miss");
+        slog::info!(log, "\x1b[33mcache miss\x1b[0m");
+        assert_eq!(capture.messages(), vec!["\x1b[33m[DEBUG-ME]\x1b[0m cache miss"]);
+    }
+
+    #[test]
+    fn filter_miss_drain() {
+        let matched = Capture::default();
+        let missed = Capture::default();
+        let logger = LogBuilder::new(matched.clone())
+                        .parse("info/foo")
+                        .filter_miss_drain(missed.clone())
+                        .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "foo 1");
+        slog::info!(log, "bar 1");
+        slog::debug!(log, "bar 2");
+        slog::info!(log, "foo 2");
+        assert_eq!(matched.messages(), vec!["foo 1", "foo 2"]);
+        assert_eq!(missed.messages(), vec!["bar 1"]);
+    }
+
+    #[test]
+    fn audit_drain() {
+        #[derive(Clone, Default)]
+        struct Audit(Arc<Mutex<Vec<String>>>);
+
+        impl slog::Drain for Audit {
+            type Ok = ();
+            type Err = slog::Never;
+            fn log(&self, info: &slog::Record, val: &slog::OwnedKVList) -> Result<(), slog::Never> {
+                let keys = ["ts", "user", "secret"].iter()
+                    .filter(|key| super::kv::has_key(info, val, key))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let entry = format!("{} {} {:?} {}", info.level().as_short_str(), info.module(), info.msg().to_string(), keys.join(","));
+                self.0.lock().unwrap().push(entry);
+                Ok(())
+            }
+        }
+
+        let capture = Capture::default();
+        let audit = Audit::default();
+        let logger = LogBuilder::new(capture.clone())
+                        .parse("info/shown")
+                        .audit_drain(audit.clone())
+                        .build();
+        let log = slog::Logger::root(logger, slog::o!("user" => "admin"));
+        slog::info!(log, "shown");
+        slog::debug!(log, "card number 1234"; "secret" => 1234);
+        slog::info!(log, "filtered, not audited");
+        assert_eq!(capture.messages(), vec!["shown"]);
+
+        let audited = audit.0.lock().unwrap().clone();
+        assert_eq!(audited, vec![format!("DEBG {} \"\" ts", module_path!())]);
+    }
+
+    #[test]
+    fn directives_to_spec_round_trip() {
+        let parsed = parse_spec("warn,crate1::mod1=error,crate1::mod2,crate2=off,*=info/a.c");
+        let spec = directives_to_spec(&parsed.directives, parsed.filter.as_deref());
+        assert_eq!(spec, "warning,crate1::mod1=error,crate1::mod2=trace,crate2=off,info/a.c");
+
+        let reparsed = parse_spec(&spec);
+        assert_eq!(reparsed.directives, parsed.directives);
+        assert_eq!(reparsed.filter, parsed.filter);
+        assert!(reparsed.errors.is_empty());
+
+        assert_eq!(directives_to_spec(&[], None), "");
+    }
+
+    #[test]
+    fn format_for() {
+        mod db {
+            pub fn log(log: &slog::Logger) {
+                slog::info!(log, "query"; "id" => 1);
+            }
+        }
+
+        mod api {
+            pub fn log(log: &slog::Logger) {
+                slog::info!(log, "say \"hi\""; "id" => 2);
+            }
+        }
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone())
+            .parse("info")
+            .format_for(&format!("{}::db", module_path!()), super::FormatKind::Full)
+            .format_for(&format!("{}::api", module_path!()), super::FormatKind::Json)
+            .build();
+        let log = slog::Logger::root(logger, slog::o!("app" => "test"));
+        db::log(&log);
+        api::log(&log);
+        slog::info!(log, "plain"; "id" => 3);
+        assert_eq!(capture.messages(), vec![
+            "query id=1 app=test".to_string(),
+            r#"{"msg":"say \"hi\"","id":"2","app":"test"}"#.to_string(),
+            "plain".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn route() {
+        mod db {
+            pub mod pool {
+                pub fn log(log: &slog::Logger) {
+                    slog::info!(log, "pool");
+                }
+            }
+
+            pub fn log(log: &slog::Logger) {
+                slog::info!(log, "db");
+            }
+        }
+
+        let main = Capture::default();
+        let db = Capture::default();
+        let pool = Capture::default();
+        let logger = LogBuilder::new(main.clone())
+                        .parse("info")
+                        .route(&format!("{}::db::pool", module_path!()), pool.clone())
+                        .route(&format!("{}::db", module_path!()), db.clone())
+                        .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "main");
+        db::log(&log);
+        db::pool::log(&log);
+        slog::debug!(log, "disabled");
+        assert_eq!(main.messages(), vec!["main"]);
+        assert_eq!(db.messages(), vec!["db"]);
+        assert_eq!(pool.messages(), vec!["pool"]);
+    }
+
+    #[test]
+    fn always_log() {
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("off").build();
+        let kv = slog::OwnedKVList::from(slog::o!());
+        static RS: slog::RecordStatic<'static> = slog::record_static!(Level::Info, "");
+        logger.always_log(&slog::Record::new(&RS, &format_args!("unconditional"), slog::b!()), &kv)
+            .unwrap();
+        logger.elevate_temporarily("crate1=bogus", Duration::from_secs(1));
+        assert_eq!(capture.messages(), vec![
+            "unconditional".to_string(),
+            "invalid logging spec 'bogus', ignoring it".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn level_set() {
+        assert!(parse_spec("crate1=warn|error,info|critical").errors.is_empty());
+        let logger = LogBuilder::new(slog::Discard).parse("crate1=warn|error,info|critical").build();
+        assert!(logger.enabled(Level::Error, "crate1"));
+        assert!(logger.enabled(Level::Warning, "crate1"));
+        assert!(!logger.enabled(Level::Info, "crate1"));
+        assert!(!logger.enabled(Level::Critical, "crate1"));
+        assert!(logger.enabled(Level::Info, "crate2"));
+        assert!(!logger.enabled(Level::Error, "crate2"));
+        assert!(logger.enabled(Level::Critical, "crate2"));
+
+        let dirs = parse_spec("crate1=error|warn").directives;
+        assert_eq!(dirs[0].level(), FilterLevel::Warning);
+        assert_eq!(directives_to_spec(&dirs, None), "crate1=error|warning");
+
+        let parsed = parse_spec("crate1=warn|bogus");
+        assert_eq!(parsed.errors, vec![ParseError::InvalidLevel("warn|bogus".to_string())]);
+    }
+
+    #[test]
+    fn len_filter() {
+        let parsed = parse_spec("info/@len>10");
+        assert_eq!(parsed.filter, None);
+        assert_eq!(parsed.len_filter, Some(LenFilter { op: Comparison::Greater, len: 10 }));
+
+        let parsed = parse_spec("info/foo@len<=5@>=warn");
+        assert_eq!(parsed.filter, Some("foo".to_string()));
+        assert_eq!(parsed.len_filter, Some(LenFilter { op: Comparison::LessOrEqual, len: 5 }));
+        assert_eq!(parsed.filter_bound, Some(FilterBound::AtLeast(Level::Warning)));
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/@len>10").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "0123456789");
+        slog::info!(log, "0123456789a");
+        assert_eq!(capture.messages(), vec!["0123456789a"]);
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/a@len<4").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "abc");
+        slog::info!(log, "abcd");
+        slog::info!(log, "bcd");
+        assert_eq!(capture.messages(), vec!["abc"]);
+    }
+
+    #[test]
+    fn level_filters() {
+        let parsed = parse_spec("debug/db;warn/;color=never;error/disk");
+        assert_eq!(parsed.filter, Some("db".to_string()));
+        assert_eq!(parsed.level_filters, vec![
+            (Level::Warning, None),
+            (Level::Error, Some("disk".to_string())),
+        ]);
+        assert_eq!(parsed.options.color, Some(Color::Never));
+        let parsed = parse_spec("info/a;b/c");
+        assert_eq!(parsed.filter, Some("a;b/c".to_string()));
+        assert!(parsed.level_filters.is_empty());
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("debug/db;warn/").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::debug!(log, "db query");
+        slog::debug!(log, "http request");
+        slog::warn!(log, "http timeout");
+        slog::error!(log, "http error");
+        assert_eq!(capture.messages(), vec!["db query", "http timeout"]);
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info")
+            .level_filter(Level::Error, Some("disk"))
+            .build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "http request");
+        slog::error!(log, "http error");
+        slog::error!(log, "disk full");
+        assert_eq!(capture.messages(), vec!["http request", "disk full"]);
+    }
+
+    #[test]
+    fn errors_only() {
+        let parsed = parse_spec("warn/@errors");
+        assert_eq!(parsed.filter, None);
+        assert!(parsed.errors_only);
+
+        let e = std::io::Error::other("disk full");
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/@errors").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::error!(log, "plain"; "err" => "not an error");
+        slog::error!(log, "failed"; "err" => #&e);
+        slog::info!(log.new(slog::o!("cause" => #std::fmt::Error)), "failed logger");
+        assert_eq!(capture.messages(), vec!["failed", "failed logger"]);
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info").errors_only().build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::warn!(log, "plain");
+        slog::warn!(log, "failed"; "err" => #e);
+        assert_eq!(capture.messages(), vec!["failed"]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "regex", feature = "regex-lite"))]
+    fn lazy_filter() {
+        let capture = Capture::default();
+        let logger = Arc::new(LogBuilder::new(capture.clone()).lazy_filter().parse("info/^fo+$").build());
+        assert!(!logger.filter.as_ref().unwrap().is_compiled());
+
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+        slog::debug!(log, "foo");
+        assert!(!logger.filter.as_ref().unwrap().is_compiled());
+        slog::info!(log, "foo");
+        slog::info!(log, "bar");
+        assert!(logger.filter.as_ref().unwrap().is_compiled());
+        assert_eq!(capture.messages(), vec!["foo"]);
+
+        // Also for filters parsed before
+        let logger = LogBuilder::new(slog::Discard).parse("info/^fo+$").lazy_filter().build();
+        assert!(!logger.filter.as_ref().unwrap().is_compiled());
+        let logger = LogBuilder::new(slog::Discard).parse("info/^fo+$").build();
+        assert!(logger.filter.as_ref().unwrap().is_compiled());
+
+        let capture = Capture::default();
+        let (builder, warnings) = LogBuilder::new(capture.clone()).collect_warnings();
+        let logger = builder.lazy_filter().parse("info/[").build();
+        assert_eq!(logger.filter_source(), Some("["));
+        assert!(warnings.lock().unwrap().is_empty());
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "bar");
+        slog::info!(log, "baz");
+        assert_eq!(capture.messages(), vec!["bar", "baz"]);
+        // Reported once, to the collector
+        let warnings = warnings.lock().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("invalid regex filter"), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn literal_prefix_filter() {
+        let logger = LogBuilder::new(slog::Discard).parse("info/lit:a.b").build();
+        let filter = logger.filter.as_ref().unwrap();
+        assert!(filter.is_match("x a.b y"));
+        assert!(!filter.is_match("axb"));
+        assert!(!filter.is_match("lit:"));
+        assert_eq!(logger.filter_source(), Some("lit:a.b"));
+
+        // Not even checked for being a valid regex
+        let logger = LogBuilder::new(slog::Discard).parse("info/lit:[").build();
+        assert!(logger.filter.as_ref().unwrap().is_match("[0]"));
+    }
+
+    #[test]
+    #[cfg(any(feature = "regex", feature = "regex-lite"))]
+    fn literal_filter() {
+        let capture = Capture::default();
+        let logger = Arc::new(LogBuilder::new(capture.clone()).lazy_filter().parse("info/time out").build());
+        let log = slog::Logger::root(logger.clone(), slog::o!());
+        slog::info!(log, "a time out");
+        slog::info!(log, "a timeout");
+        assert_eq!(capture.messages(), vec!["a time out"]);
+        // Searched for as a substring, without the regex engine
+        assert!(!logger.filter.as_ref().unwrap().is_compiled());
+
+        let logger = LogBuilder::new(slog::Discard).parse("info/time.out").build();
+        assert!(logger.filter.as_ref().unwrap().is_match("a time-out"));
+    }
+
+    #[test]
+    fn kv_threshold() {
+        let parsed = parse_spec("info/@duration_ms>500");
+        assert_eq!(parsed.filter, None);
+        assert_eq!(parsed.kv_threshold,
+                   Some(KvThresholdFilter { key: "duration_ms".to_string(), op: Comparison::Greater, threshold: 500.0 }));
+        let parsed = parse_spec("info/slow@load<=0.5");
+        assert_eq!(parsed.filter, Some("slow".to_string()));
+        assert_eq!(parsed.kv_threshold.map(|filter| filter.op), Some(Comparison::LessOrEqual));
+        for spec in ["info/@duration_ms>", "info/@>5", "info/@a b>5", "info/@x>NaN"] {
+            assert_eq!(parse_spec(spec).kv_threshold, None, "{}", spec);
+        }
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/@duration_ms>=500").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "fast"; "duration_ms" => 20);
+        slog::info!(log, "slow"; "duration_ms" => 500u64);
+        slog::info!(log, "slower"; "duration_ms" => 1500.5);
+        slog::info!(log, "as string"; "duration_ms" => "700");
+        slog::info!(log, "not a number"; "duration_ms" => "long");
+        slog::info!(log, "missing");
+        slog::info!(log.new(slog::o!("duration_ms" => -1)), "negative");
+        assert_eq!(capture.messages(), vec!["slow", "slower", "as string"]);
+    }
+
+    #[test]
+    fn key_filter() {
+        let parsed = parse_spec("info/@has:trace_id");
+        assert_eq!(parsed.filter, None);
+        assert_eq!(parsed.key_filter, Some(KeyPresenceFilter { key: "trace_id".to_string() }));
+        let parsed = parse_spec("info/foo@has:");
+        assert_eq!(parsed.filter, Some("foo@has:".to_string()));
+        assert_eq!(parsed.key_filter, None);
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/@has:trace_id").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "untraced"; "user" => 1);
+        slog::info!(log, "traced"; "user" => 1, "trace_id" => 2);
+        let traced = log.new(slog::o!("trace_id" => 3));
+        slog::info!(traced, "traced logger");
+        assert_eq!(capture.messages(), vec!["traced", "traced logger"]);
+
+        let capture = Capture::default();
+        let logger = LogBuilder::new(capture.clone()).parse("info/traced@has:trace_id").build();
+        let log = slog::Logger::root(logger, slog::o!());
+        slog::info!(log, "traced"; "user" => 1);
+        slog::info!(log, "other"; "trace_id" => 2);
+        slog::info!(log, "traced"; "trace_id" => 2);
+        assert_eq!(capture.messages(), vec!["traced"]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "regex", feature = "regex-lite"))]
+    fn regex_filter() {
+        let filter = filter::Filter::new("^f.o+$").unwrap();
+        assert!(filter.is_match("foooo"));
+        assert!(filter.is_match("f1o"));
+        assert!(!filter.is_match("a foo"));
+        assert_eq!(filter.to_string(), "^f.o+$");
+        assert!(filter::Filter::new("[").is_err());
     }
 
     #[test]
@@ -0,0 +1,969 @@
+//! Parsing of logging specifications
+//!
+//! Everything in here only depends on `core` and `alloc`, so it's available
+//! without the `std` feature. Problems are returned as `ParseError`s instead
+//! of being printed.
+
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::fmt;
+use alloc::sync::Arc;
+use core::panic::{RefUnwindSafe, UnwindSafe};
+
+use slog::{FilterLevel, Level};
+
+/// Prefix of filters matched as plain substring, even with a regex engine
+#[cfg(feature = "std")]
+pub(crate) const LITERAL_PREFIX: &str = "lit:";
+
+/// A single logging directive, eg. `crate1::mod1=debug`
+#[derive(Clone, Debug)]
+pub struct LogDirective {
+    pub(crate) name: Option<String>,
+    pub(crate) level: FilterLevel,
+    pub(crate) levels: Option<LevelSet>,
+    pub(crate) matcher: Option<Arc<dyn ModuleMatcher>>,
+    pub(crate) priority: u8,
+}
+
+impl PartialEq for LogDirective {
+    fn eq(&self, other: &LogDirective) -> bool {
+        let same_matcher = match (&self.matcher, &other.matcher) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        self.name == other.name && self.level == other.level && self.levels == other.levels && same_matcher &&
+            self.priority == other.priority
+    }
+}
+
+impl Eq for LogDirective {}
+
+impl LogDirective {
+    /// A directive enabling `level` for modules starting with `name`, or for
+    /// all modules if it's `None`
+    pub fn new(name: Option<&str>, level: FilterLevel) -> Self {
+        LogDirective {
+            name: name.map(|name| name.to_string()),
+            level,
+            levels: None,
+            matcher: None,
+            priority: 0,
+        }
+    }
+
+    /// A directive enabling `level` for modules `matcher` matches
+    ///
+    /// Directives with a matcher take precedence over ones with a module
+    /// prefix. Among each other, they're equally specific, so if several
+    /// match, the most verbose one wins, unless `LogBuilder::prefer_quieter`
+    /// is set.
+    pub fn with_matcher<M: ModuleMatcher + 'static>(matcher: M, level: FilterLevel) -> Self {
+        LogDirective {
+            name: None,
+            level,
+            levels: None,
+            matcher: Some(Arc::new(matcher)),
+            priority: 0,
+        }
+    }
+
+    /// Module prefix this directive applies to (`None` for all modules)
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Maximum level this directive enables
+    pub fn level(&self) -> FilterLevel {
+        self.level
+    }
+
+    /// Priority of this directive, `0` unless raised by a leading `!`
+    ///
+    /// Among the directives matching a module, the ones of the highest
+    /// priority are considered only, and the usual rules pick one of them.
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// The same directive with priority `priority`
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Exact levels this directive enables, for directives like
+    /// `crate1=warn|error`
+    pub fn levels(&self) -> Option<LevelSet> {
+        self.levels
+    }
+
+    /// Whether records at `level` are enabled by this directive
+    pub fn enables(&self, level: Level) -> bool {
+        match self.levels {
+            Some(levels) => levels.contains(level),
+            None => level.as_usize() <= self.level.as_usize(),
+        }
+    }
+}
+
+/// Decides which modules a directive applies to
+///
+/// Directives naming a module, like the ones parsed from a spec, match by
+/// prefix without a matcher, as that honours the logger's options such as
+/// `LogBuilder::case_insensitive_modules`. Other strategies can be used with
+/// `LogDirective::with_matcher`.
+pub trait ModuleMatcher: fmt::Debug + Send + Sync + RefUnwindSafe + UnwindSafe {
+    /// Whether the directive applies to `module`
+    fn matches(&self, module: &str) -> bool;
+
+    /// The pattern, if this matches modules like a `GlobMatcher` with it
+    ///
+    /// With the `globset` feature, directives with such matchers are
+    /// matched all at once instead of one by one.
+    fn glob(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Matches exactly one module, but none of its submodules
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExactMatcher(pub String);
+
+impl ModuleMatcher for ExactMatcher {
+    fn matches(&self, module: &str) -> bool {
+        module == self.0
+    }
+}
+
+/// Matches modules ending with a suffix, eg. `::tests`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuffixMatcher(pub String);
+
+impl ModuleMatcher for SuffixMatcher {
+    fn matches(&self, module: &str) -> bool {
+        module.ends_with(&self.0)
+    }
+}
+
+/// Matches modules against a glob, where `*` stands for any number of
+/// characters and `?` for a single one, eg. `*::db::*`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlobMatcher(pub String);
+
+impl ModuleMatcher for GlobMatcher {
+    fn matches(&self, module: &str) -> bool {
+        glob_matches(self.0.as_bytes(), module.as_bytes())
+    }
+
+    fn glob(&self) -> Option<&str> {
+        Some(&self.0)
+    }
+}
+
+/// Match `s` against `pattern`, backtracking to the last `*`
+fn glob_matches(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    let mut star = None;
+    while i < s.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == s[i]) {
+            p += 1;
+            i += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, i));
+            p += 1;
+        } else if let Some((star_p, star_i)) = star {
+            // Let the last `*` take one more character
+            p = star_p + 1;
+            i = star_i + 1;
+            star = Some((star_p, star_i + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// A set of explicitly selected levels, eg. `warn|error`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LevelSet(u8);
+
+impl LevelSet {
+    /// An empty set
+    pub fn new() -> Self {
+        LevelSet(0)
+    }
+
+    /// Add `level` to the set
+    pub fn insert(&mut self, level: Level) {
+        self.0 |= 1 << level.as_usize();
+    }
+
+    /// Whether `level` is in the set
+    pub fn contains(&self, level: Level) -> bool {
+        self.0 & (1 << level.as_usize()) != 0
+    }
+
+    /// Most verbose level in the set, `Off` if it's empty
+    pub fn max(&self) -> FilterLevel {
+        (1..=Level::Trace.as_usize()).rev()
+            .find(|&i| self.0 & (1 << i) != 0)
+            .and_then(FilterLevel::from_usize)
+            .unwrap_or(FilterLevel::Off)
+    }
+
+    /// Parse a pipe-separated set like `warn|error`
+    fn parse(s: &str) -> Option<LevelSet> {
+        let mut set = LevelSet::new();
+        for level in s.split('|') {
+            set.insert(parse_record_level(level)?);
+        }
+        Some(set)
+    }
+}
+
+impl fmt::Display for LevelSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for i in 1..=Level::Trace.as_usize() {
+            if self.0 & (1 << i) != 0 {
+                if !first {
+                    f.write_str("|")?;
+                }
+                first = false;
+                let level = Level::from_usize(i).expect("valid level index");
+                f.write_str(&level.as_str().to_ascii_lowercase())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a level the way specs do
+///
+/// Accepted are, ignoring case and surrounding whitespace, the level names
+/// (`off`, `critical`, `error`, `warning`, `info`, `debug` and `trace`), any
+/// prefix of them (eg. `d` or `deb`), slog's short names (`crit`, `erro`,
+/// `debg`, `trce`), and the numbers `0` (off) to `6` (trace).
+pub fn parse_filter_level(s: &str) -> Option<FilterLevel> {
+    let s = s.trim();
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        s.parse().ok().and_then(FilterLevel::from_usize)
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// Parse the level of a record, like `parse_filter_level` but without `off`
+pub(crate) fn parse_record_level(s: &str) -> Option<Level> {
+    parse_filter_level(s).and_then(|level| Level::from_usize(level.as_usize()))
+}
+
+/// Parse a level, or a pipe-separated set of levels
+pub(crate) fn parse_level(s: &str) -> Option<(FilterLevel, Option<LevelSet>)> {
+    if s.contains('|') {
+        LevelSet::parse(s).map(|set| (set.max(), Some(set)))
+    } else {
+        parse_filter_level(s).map(|level| (level, None))
+    }
+}
+
+/// Parse the `level:module` shorthand, eg. `d:app`
+///
+/// Colons only come in pairs in module paths, so a single `:` after a level
+/// tells it apart from a module name like `d::app`.
+fn parse_shorthand(s: &str) -> Option<((FilterLevel, Option<LevelSet>), &str)> {
+    let (level, name) = s.split_once(':')?;
+    if name.is_empty() || name.starts_with(':') {
+        return None;
+    }
+    parse_level(level).map(|level| (level, name))
+}
+
+/// A directive matching records by a key-value, eg. `@build=8jdkj2df=debug`
+///
+/// The key is looked up in the record's own key-values and the ones of the
+/// logger it was logged through.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KvDirective {
+    pub(crate) key: String,
+    pub(crate) value: String,
+    pub(crate) level: FilterLevel,
+}
+
+impl KvDirective {
+    /// Key to look up
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Value the key has to have
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Maximum level this directive enables
+    pub fn level(&self) -> FilterLevel {
+        self.level
+    }
+}
+
+/// A directive adjusting the global level for a module, eg. `noisy=-1`
+///
+/// Positive offsets are more verbose. It's resolved against the global
+/// level when building a logger, see also `ParsedSpec::resolved_directives`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelativeDirective {
+    pub(crate) name: Option<String>,
+    pub(crate) offset: i8,
+}
+
+impl RelativeDirective {
+    /// Module prefix this directive applies to, `None` for all modules
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Number of levels to adjust the global level by
+    pub fn offset(&self) -> i8 {
+        self.offset
+    }
+
+    /// The directive for `global` adjusted by the offset, clamped to the
+    /// range from `Off` to `Trace`
+    pub fn resolve(&self, global: FilterLevel) -> LogDirective {
+        let level = global.as_usize() as isize + self.offset as isize;
+        let level = level.clamp(FilterLevel::Off.as_usize() as isize, FilterLevel::Trace.as_usize() as isize);
+        LogDirective::new(self.name.as_deref(), FilterLevel::from_usize(level as usize).unwrap_or(global))
+    }
+}
+
+/// Problem found while parsing a logging specification
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// A directive was ignored, because its level couldn't be parsed
+    InvalidLevel(String),
+    /// A directive was ignored, because it's malformed (eg. has more than one `=`)
+    InvalidDirective(String),
+    /// A `;key=value` option was ignored, because its value is invalid
+    InvalidOption(String),
+    /// The filter was ignored, because it doesn't compile
+    InvalidFilter(FilterError),
+    /// A `@profile:name` directive was ignored, because no profile of that
+    /// name is registered
+    UnknownProfile(String),
+}
+
+/// Why a filter doesn't compile
+///
+/// With the `std` feature, the underlying error (eg. `regex::Error`) is
+/// available as `source()` of the `ParseError`.
+#[derive(Clone, Debug)]
+pub struct FilterError {
+    message: String,
+    #[cfg(feature = "std")]
+    source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+impl FilterError {
+    /// Human readable description of the problem
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    #[cfg(all(feature = "std", any(feature = "regex", feature = "regex-lite")))]
+    pub(crate) fn with_source<E: std::error::Error + Send + Sync + 'static>(e: E) -> Self {
+        FilterError {
+            message: e.to_string(),
+            source: Some(Arc::new(e)),
+        }
+    }
+}
+
+impl PartialEq for FilterError {
+    fn eq(&self, other: &FilterError) -> bool {
+        self.message == other.message
+    }
+}
+
+impl Eq for FilterError {}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            ParseError::InvalidFilter(ref e) => e.source.as_ref().map(|e| &**e as &(dyn std::error::Error + 'static)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::InvalidLevel(ref s) | ParseError::InvalidDirective(ref s) =>
+                write!(f, "invalid logging spec '{}', ignoring it", s),
+            ParseError::InvalidOption(ref s) =>
+                write!(f, "invalid logging option '{}', ignoring it", s),
+            ParseError::InvalidFilter(ref e) =>
+                write!(f, "invalid regex filter - {}", e),
+            ParseError::UnknownProfile(ref name) =>
+                write!(f, "unknown logging profile '{}', ignoring it", name),
+        }
+    }
+}
+
+/// Color mode requested with the `color` option
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    /// Use colors if the output is a terminal
+    Auto,
+    /// Always use colors
+    Always,
+    /// Never use colors
+    Never,
+}
+
+/// Restricts the filter to records on one side of a level
+///
+/// Written as a `@` suffix of the filter, eg. `/foo@<info` only filters
+/// debug and trace records, while more severe ones always pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterBound {
+    /// `@>=level`: records at `level` or more severe
+    AtLeast(Level),
+    /// `@>level`: records more severe than `level`
+    Above(Level),
+    /// `@<=level`: records at `level` or less severe
+    AtMost(Level),
+    /// `@<level`: records less severe than `level`
+    Below(Level),
+}
+
+impl FilterBound {
+    /// Whether the filter applies to records at `level`
+    pub fn applies_to(&self, level: Level) -> bool {
+        match *self {
+            FilterBound::AtLeast(bound) => level.as_usize() <= bound.as_usize(),
+            FilterBound::Above(bound) => level.as_usize() < bound.as_usize(),
+            FilterBound::AtMost(bound) => level.as_usize() >= bound.as_usize(),
+            FilterBound::Below(bound) => level.as_usize() > bound.as_usize(),
+        }
+    }
+
+    fn parse(s: &str) -> Option<FilterBound> {
+        let (bound, level): (fn(Level) -> FilterBound, &str) =
+            if let Some(level) = s.strip_prefix(">=") {
+                (FilterBound::AtLeast, level)
+            } else if let Some(level) = s.strip_prefix("<=") {
+                (FilterBound::AtMost, level)
+            } else if let Some(level) = s.strip_prefix('>') {
+                (FilterBound::Above, level)
+            } else if let Some(level) = s.strip_prefix('<') {
+                (FilterBound::Below, level)
+            } else {
+                return None;
+            };
+        parse_record_level(level).map(bound)
+    }
+}
+
+/// Comparison operator of filter predicates like `@len>1000`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+    /// `==`
+    Equal,
+}
+
+impl Comparison {
+    /// Compare `a` to `b`, eg. `a < b` for `Less`
+    pub fn compare<T: PartialOrd>(&self, a: T, b: T) -> bool {
+        match *self {
+            Comparison::Less => a < b,
+            Comparison::LessOrEqual => a <= b,
+            Comparison::Greater => a > b,
+            Comparison::GreaterOrEqual => a >= b,
+            Comparison::Equal => a == b,
+        }
+    }
+
+    /// Split a leading operator off `s`
+    fn split(s: &str) -> Option<(Comparison, &str)> {
+        for &(op, cmp) in &[("<=", Comparison::LessOrEqual),
+                            (">=", Comparison::GreaterOrEqual),
+                            ("==", Comparison::Equal),
+                            ("<", Comparison::Less),
+                            (">", Comparison::Greater)] {
+            if let Some(rest) = s.strip_prefix(op) {
+                return Some((cmp, rest));
+            }
+        }
+        None
+    }
+}
+
+/// Filter on the byte length of the formatted message, eg. `@len>1000`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LenFilter {
+    /// How the length is compared
+    pub op: Comparison,
+    /// Length to compare to
+    pub len: usize,
+}
+
+impl LenFilter {
+    /// Whether a message of `len` bytes passes
+    pub fn matches(&self, len: usize) -> bool {
+        self.op.compare(len, self.len)
+    }
+
+    fn parse(s: &str) -> Option<LenFilter> {
+        let (op, len) = Comparison::split(s.strip_prefix("len")?)?;
+        len.parse().ok().map(|len| LenFilter { op, len })
+    }
+}
+
+/// Filter on the presence of a key in the record's key-values, eg.
+/// `@has:trace_id`
+///
+/// Only the keys are looked at, values aren't formatted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyPresenceFilter {
+    /// Key the record, or the logger it's logged through, has to carry
+    pub key: String,
+}
+
+impl KeyPresenceFilter {
+    fn parse(s: &str) -> Option<KeyPresenceFilter> {
+        match s.strip_prefix("has:")? {
+            "" => None,
+            key => Some(KeyPresenceFilter { key: key.to_string() }),
+        }
+    }
+}
+
+/// Filter on a numeric key-value, eg. `@duration_ms>500`
+///
+/// The value is formatted and parsed as a number, so strings holding one
+/// are compared too.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KvThresholdFilter {
+    /// Key whose value is compared
+    pub key: String,
+    /// How the value is compared
+    pub op: Comparison,
+    /// Number to compare to
+    pub threshold: f64,
+}
+
+impl KvThresholdFilter {
+    /// Whether the formatted `value` is a number passing the threshold
+    pub fn matches(&self, value: &str) -> bool {
+        value.trim().parse::<f64>().is_ok_and(|value| self.op.compare(value, self.threshold))
+    }
+
+    fn parse(s: &str) -> Option<KvThresholdFilter> {
+        let i = s.find(['<', '>', '='])?;
+        let key = &s[..i];
+        if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+            return None;
+        }
+        let (op, threshold) = Comparison::split(&s[i..])?;
+        let threshold = threshold.parse::<f64>().ok().filter(|threshold| !threshold.is_nan())?;
+        Some(KvThresholdFilter { key: key.to_string(), op, threshold })
+    }
+}
+
+/// Global options following the directives, eg. `info;color=never;sample=10`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpecOptions {
+    /// `color=auto|always|never`: color mode of the `init()` output
+    pub color: Option<Color>,
+    /// `sample=N`: only log every N-th record that passes filtering
+    pub sample: Option<u32>,
+    /// `filter-disable=true|false`: ignore the `/` filter
+    pub filter_disable: bool,
+}
+
+/// Result of parsing a logging specification
+#[derive(Clone, Debug, Default)]
+pub struct ParsedSpec {
+    /// Directives that parsed successfully, in order of appearance
+    pub directives: Vec<LogDirective>,
+    /// Key-value directives (`@key=value=level`), in order of appearance
+    pub kv_directives: Vec<KvDirective>,
+    /// Relative directives (`module=+N` or `module=-N`), in order of appearance
+    pub relative_directives: Vec<RelativeDirective>,
+    /// The (uncompiled) filter following the `/`, if any
+    pub filter: Option<String>,
+    /// Level bound of the filter, if any
+    pub filter_bound: Option<FilterBound>,
+    /// Message length filter, if any
+    pub len_filter: Option<LenFilter>,
+    /// Key presence filter, if any
+    pub key_filter: Option<KeyPresenceFilter>,
+    /// Numeric key-value filter, if any
+    pub kv_threshold: Option<KvThresholdFilter>,
+    /// Whether only records carrying an error pass (`@errors`)
+    pub errors_only: bool,
+    /// Filters for single levels (`;level/pattern`), in order of appearance,
+    /// `None` for none
+    pub level_filters: Vec<(Level, Option<String>)>,
+    /// Options following the directives and filter
+    pub options: SpecOptions,
+    /// Everything that was ignored, and why
+    pub errors: Vec<ParseError>,
+}
+
+impl ParsedSpec {
+    /// The directives, followed by the relative directives resolved against
+    /// the last global one, or against `default` without a global directive
+    ///
+    /// For passing all module directives of a spec on where only
+    /// `LogDirective`s are taken, eg. to `directives_to_spec`.
+    pub fn resolved_directives(&self, default: FilterLevel) -> Vec<LogDirective> {
+        let mut directives = self.directives.clone();
+        resolve_relative(&mut directives, &self.relative_directives, default);
+        directives
+    }
+}
+
+/// Append `relative` to `directives`, resolved against the last global
+/// directive among them, or against `default` if there's none
+pub(crate) fn resolve_relative(directives: &mut Vec<LogDirective>, relative: &[RelativeDirective], default: FilterLevel) {
+    if relative.is_empty() {
+        return;
+    }
+    let global = directives.iter().rev()
+        .find(|d| d.name.is_none() && d.matcher.is_none())
+        .map_or(default, |d| d.level);
+    directives.extend(relative.iter().map(|d| d.resolve(global)));
+}
+
+/// What happened to the parts of a logging specification
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseReport {
+    /// Directives that were applied
+    pub accepted: Vec<LogDirective>,
+    /// Parts that were ignored, and why
+    pub rejected: Vec<ParseError>,
+}
+
+impl ParseReport {
+    /// Number of ignored parts
+    pub fn dropped(&self) -> usize {
+        self.rejected.len()
+    }
+}
+
+/// Parse a logging specification string (e.g: "crate1,crate2::mod3,crate3::x=error/foo")
+///
+/// Parsing is lenient: invalid directives are skipped and reported in
+/// `ParsedSpec::errors`.
+pub fn parse_spec(spec: &str) -> ParsedSpec {
+    let mut parsed = ParsedSpec::default();
+
+    let spec = parse_options(spec, &mut parsed);
+
+    // Kill switch: disable all logging, without looking any further
+    let trimmed = spec.trim();
+    if ["off", "0", "none"].iter().any(|off| trimmed.eq_ignore_ascii_case(off)) {
+        parsed.directives.push(LogDirective {
+            name: None,
+            level: FilterLevel::Off,
+            levels: None,
+            matcher: None,
+            priority: 0,
+        });
+        return parsed;
+    }
+
+    // Only the first `/` separates the filter, so it can contain slashes
+    let (mods, filter) = match spec.split_once('/') {
+        Some((mods, filter)) => (Some(mods), Some(filter)),
+        None => (Some(spec), None),
+    };
+    // One allocation for the directives, even for long specs
+    parsed.directives.reserve(mods.map_or(0, |m| m.matches(',').count() + 1));
+    if let Some(m) = mods { for s in m.split(',') {
+        if s.is_empty() { continue }
+        // Each leading `!` raises the priority of the directive
+        let directive = s.trim_start_matches('!');
+        let priority = (s.len() - directive.len()).min(u8::MAX as usize) as u8;
+        if let Some(kv) = directive.strip_prefix('@') {
+            if priority > 0 {
+                parsed.errors.push(ParseError::InvalidDirective(s.to_string()));
+                continue
+            }
+            parse_kv_directive(s, kv, &mut parsed);
+            continue
+        }
+        let mut parts = directive.split('=');
+        let ((log_level, levels), name) = match (parts.next(), parts.next().map(|s| s.trim()), parts.next()) {
+            (Some(part0), None, None) => {
+                // if the single argument is a log-level string or number,
+                // treat that as a global fallback
+                match parse_level(part0) {
+                    Some(level) => (level, None),
+                    None => match parse_shorthand(part0) {
+                        Some((level, name)) => (level, Some(name)),
+                        None => ((FilterLevel::max(), None), Some(part0)),
+                    },
+                }
+            }
+            (Some(part0), Some(""), None) => ((FilterLevel::max(), None), Some(part0)),
+            (Some(_), Some(part1), None) if part1.starts_with(['+', '-']) && priority > 0 => {
+                parsed.errors.push(ParseError::InvalidDirective(s.to_string()));
+                continue
+            },
+            (Some(part0), Some(part1), None) if part1.starts_with(['+', '-']) => {
+                match part1.parse() {
+                    Ok(offset) => parsed.relative_directives.push(RelativeDirective {
+                        // `*=+1` adjusts the global level itself
+                        name: Some(part0).filter(|name| *name != "*").map(|name| name.to_string()),
+                        offset,
+                    }),
+                    Err(_) => parsed.errors.push(ParseError::InvalidLevel(part1.to_string())),
+                }
+                continue
+            },
+            (Some(part0), Some(part1), None) => {
+                match parse_level(part1) {
+                    Some(level) => (level, Some(part0)),
+                    None => {
+                        parsed.errors.push(ParseError::InvalidLevel(part1.to_string()));
+                        continue
+                    }
+                }
+            },
+            _ => {
+                parsed.errors.push(ParseError::InvalidDirective(s.to_string()));
+                continue
+            }
+        };
+        // `*` stands for all modules, same as leaving out the name
+        let name = name.filter(|name| *name != "*");
+        parsed.directives.push(LogDirective {
+            name: name.map(|s| s.to_string()),
+            level: log_level,
+            levels,
+            matcher: None,
+            priority,
+        });
+    }}
+
+    parsed.filter = filter.and_then(|filter| parse_filter_suffixes(filter, &mut parsed));
+    parsed
+}
+
+/// Strip `@` suffixes (level bound, predicates) off `filter`
+///
+/// A trailing `@` only introduces a suffix if one can be parsed from it,
+/// otherwise it's part of the filter. Returns what's left of the filter,
+/// or `None` if it consisted of suffixes only.
+fn parse_filter_suffixes(mut filter: &str, parsed: &mut ParsedSpec) -> Option<String> {
+    let mut stripped = false;
+    while let Some(i) = filter.rfind('@') {
+        let suffix = &filter[i + 1..];
+        if let Some(bound) = FilterBound::parse(suffix) {
+            parsed.filter_bound = Some(bound);
+        } else if let Some(len_filter) = LenFilter::parse(suffix) {
+            parsed.len_filter = Some(len_filter);
+        } else if let Some(key_filter) = KeyPresenceFilter::parse(suffix) {
+            parsed.key_filter = Some(key_filter);
+        } else if let Some(kv_threshold) = KvThresholdFilter::parse(suffix) {
+            parsed.kv_threshold = Some(kv_threshold);
+        } else if suffix == "errors" {
+            parsed.errors_only = true;
+        } else {
+            break;
+        }
+        filter = &filter[..i];
+        stripped = true;
+    }
+
+    if stripped && filter.is_empty() {
+        None
+    } else {
+        Some(filter.to_string())
+    }
+}
+
+/// Parse `key=value[=level]` of the `@key=value[=level]` directive `s`
+fn parse_kv_directive(s: &str, kv: &str, parsed: &mut ParsedSpec) {
+    let mut parts = kv.split('=');
+    let (key, value, level) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(key), Some(value), None, None) => (key, value, FilterLevel::max()),
+        (Some(key), Some(value), Some(level), None) => match parse_filter_level(level) {
+            Some(level) => (key, value, level),
+            None => {
+                parsed.errors.push(ParseError::InvalidLevel(level.to_string()));
+                return
+            }
+        },
+        _ => {
+            parsed.errors.push(ParseError::InvalidDirective(s.to_string()));
+            return
+        }
+    };
+    if key.is_empty() {
+        parsed.errors.push(ParseError::InvalidDirective(s.to_string()));
+        return
+    }
+    parsed.kv_directives.push(KvDirective {
+        key: key.to_string(),
+        value: value.to_string(),
+        level,
+    });
+}
+
+/// Render directives and a filter back into a logging specification
+///
+/// This is the inverse of `parse_spec`: parsing the result yields the same
+/// directives and filter. Directives with a custom `ModuleMatcher` can't be
+/// expressed in a spec, and are left out. Relative directives of a parsed
+/// spec are only included once resolved, with
+/// `ParsedSpec::resolved_directives`.
+pub fn directives_to_spec(dirs: &[LogDirective], filter: Option<&str>) -> String {
+    let mut spec = String::new();
+    for (i, directive) in dirs.iter().filter(|d| d.matcher.is_none()).enumerate() {
+        if i > 0 {
+            spec.push(',');
+        }
+        for _ in 0..directive.priority {
+            spec.push('!');
+        }
+        if let Some(ref name) = directive.name {
+            spec.push_str(name);
+            spec.push('=');
+        }
+        match directive.levels {
+            Some(levels) => spec.push_str(&levels.to_string()),
+            None => spec.push_str(&directive.level.as_str().to_ascii_lowercase()),
+        }
+    }
+    if let Some(filter) = filter {
+        spec.push('/');
+        spec.push_str(filter);
+    }
+    spec
+}
+
+/// Strip `#` comments, running to the end of the line, from a spec
+///
+/// The remaining non-empty lines are joined with `,`, so a spec can be
+/// spread over several lines, with a filter only on the last one.
+pub fn strip_comments(spec: &str) -> Cow<'_, str> {
+    if !spec.contains('#') && !spec.contains('\n') {
+        return Cow::Borrowed(spec);
+    }
+
+    let mut stripped = String::with_capacity(spec.len());
+    for line in spec.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !stripped.is_empty() {
+            stripped.push(',');
+        }
+        stripped.push_str(line);
+    }
+    Cow::Owned(stripped)
+}
+
+/// Prefix of directives standing for a profile, see `LogBuilder::profile`
+#[cfg(feature = "std")]
+const PROFILE_PREFIX: &str = "@profile:";
+
+/// Split the `@profile:name` directives off `spec`
+///
+/// Returns the profile names, in order of appearance, and the rest of the
+/// spec. Profiles are only looked for among the directives, not in the filter
+/// or options.
+#[cfg(feature = "std")]
+pub(crate) fn split_profiles(spec: &str) -> (Vec<&str>, Cow<'_, str>) {
+    let (mods, rest) = spec.split_at(spec.find(['/', ';']).unwrap_or(spec.len()));
+    if !mods.contains(PROFILE_PREFIX) {
+        return (Vec::new(), Cow::Borrowed(spec));
+    }
+
+    let mut profiles = Vec::new();
+    let mut kept = Vec::new();
+    for part in mods.split(',') {
+        match part.trim().strip_prefix(PROFILE_PREFIX) {
+            Some(name) => profiles.push(name),
+            None => kept.push(part),
+        }
+    }
+    (profiles, Cow::Owned(kept.join(",") + rest))
+}
+
+const OPTION_KEYS: &[&str] = &["color", "sample", "filter-disable"];
+
+/// Strip trailing `;key=value` options off `spec` and parse them into
+/// `parsed.options`
+///
+/// Only segments with a known key are taken, so a `;` that is part of the
+/// filter stays where it is.
+fn parse_options<'a>(mut spec: &'a str, parsed: &mut ParsedSpec) -> &'a str {
+    let mut options = Vec::new();
+    let mut level_filters = Vec::new();
+    while let Some(i) = spec.rfind(';') {
+        let part = &spec[i + 1..];
+        match part.split_once('=') {
+            Some((key, value)) if OPTION_KEYS.contains(&key) => options.push((key, value)),
+            _ => match part.split_once('/').and_then(|(level, filter)| Some((parse_record_level(level)?, filter))) {
+                Some((level, "")) => level_filters.push((level, None)),
+                Some((level, filter)) => level_filters.push((level, Some(filter.to_string()))),
+                None => break,
+            },
+        }
+        spec = &spec[..i];
+    }
+    level_filters.reverse();
+    parsed.level_filters = level_filters;
+
+    for (key, value) in options.into_iter().rev() {
+        let ok = match key {
+            "color" => match value {
+                "auto" => { parsed.options.color = Some(Color::Auto); true },
+                "always" => { parsed.options.color = Some(Color::Always); true },
+                "never" => { parsed.options.color = Some(Color::Never); true },
+                _ => false,
+            },
+            "sample" => match value.parse() {
+                Ok(n) if n > 0 => { parsed.options.sample = Some(n); true },
+                _ => false,
+            },
+            _ => match value.parse() {
+                Ok(b) => { parsed.options.filter_disable = b; true },
+                Err(_) => false,
+            },
+        };
+        if !ok {
+            let mut option = key.to_string();
+            option.push('=');
+            option.push_str(value);
+            parsed.errors.push(ParseError::InvalidOption(option));
+        }
+    }
+
+    spec
+}
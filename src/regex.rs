@@ -1,28 +1,82 @@
 extern crate regex;
 
 use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::parse::{FilterError, ParseError, LITERAL_PREFIX};
 
 use self::regex::Regex;
 
 pub struct Filter {
-    inner: Regex,
+    pattern: String,
+    // Whether the pattern has no special characters, so a substring search
+    // does, without compiling it
+    literal: bool,
+    // `None` if a lazily compiled pattern turned out to be invalid
+    inner: OnceLock<Option<Regex>>,
+    // Collector of `LogBuilder::collect_warnings` to report that to
+    warnings: Option<Arc<Mutex<Vec<String>>>>,
 }
 
 impl Filter {
-    pub fn new(spec: &str) -> Result<Filter, String> {
+    pub fn new(spec: &str) -> Result<Filter, FilterError> {
+        if spec.starts_with(LITERAL_PREFIX) {
+            return Ok(Filter::lazy(spec));
+        }
         match Regex::new(spec){
-            Ok(r) => Ok(Filter { inner: r }),
-            Err(e) => Err(e.to_string()),
+            Ok(r) => Ok(Filter { pattern: spec.to_string(), literal: is_literal(spec), inner: OnceLock::from(Some(r)), warnings: None }),
+            Err(e) => Err(FilterError::with_source(e)),
         }
     }
 
+    /// A filter compiled on first use
+    ///
+    /// If `spec` doesn't compile, a warning is reported then, see
+    /// `report_to`, and the filter matches everything, like when no filter
+    /// is set.
+    pub fn lazy(spec: &str) -> Filter {
+        Filter { pattern: spec.to_string(), literal: is_literal(spec), inner: OnceLock::new(), warnings: None }
+    }
+
+    /// Report the pattern not compiling to `warnings`, instead of printing it
+    pub fn report_to(&mut self, warnings: Option<Arc<Mutex<Vec<String>>>>) {
+        self.warnings = warnings;
+    }
+
+    /// The pattern the filter was built from
+    pub fn source(&self) -> &str {
+        &self.pattern
+    }
+
+    #[cfg(test)]
+    pub fn is_compiled(&self) -> bool {
+        self.inner.get().is_some()
+    }
+
     pub fn is_match(&self, s: &str) -> bool {
-        self.inner.is_match(s)
+        if self.literal {
+            return s.contains(self.pattern.strip_prefix(LITERAL_PREFIX).unwrap_or(&self.pattern));
+        }
+        let inner = self.inner.get_or_init(|| match Regex::new(&self.pattern) {
+            Ok(r) => Some(r),
+            Err(e) => {
+                let error = ParseError::InvalidFilter(FilterError::with_source(e));
+                crate::report_warning(self.warnings.as_deref(), error.to_string());
+                None
+            }
+        });
+        inner.as_ref().is_none_or(|r| r.is_match(s))
     }
 }
 
+/// Whether `spec` is a plain substring, eg. `timeout` or `lit:time.?out`,
+/// but not `time.?out`
+fn is_literal(spec: &str) -> bool {
+    spec.starts_with(LITERAL_PREFIX) || regex::escape(spec) == spec
+}
+
 impl fmt::Display for Filter {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.inner.fmt(f)
+        self.pattern.fmt(f)
     }
 }
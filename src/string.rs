@@ -1,16 +1,32 @@
 use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::parse::{FilterError, LITERAL_PREFIX};
 
 pub struct Filter {
     inner: String,
 }
 
 impl Filter {
-    pub fn new(spec: &str) -> Result<Filter, String> {
+    pub fn new(spec: &str) -> Result<Filter, FilterError> {
         Ok(Filter { inner: spec.to_string() })
     }
 
+    /// Substring filters need no compilation, so this is the same as `new`
+    pub fn lazy(spec: &str) -> Filter {
+        Filter { inner: spec.to_string() }
+    }
+
+    /// Substring filters are always valid, so there's nothing to report
+    pub fn report_to(&mut self, _warnings: Option<Arc<Mutex<Vec<String>>>>) {}
+
+    /// The pattern the filter was built from
+    pub fn source(&self) -> &str {
+        &self.inner
+    }
+
     pub fn is_match(&self, s: &str) -> bool {
-        s.contains(&self.inner)
+        s.contains(self.inner.strip_prefix(LITERAL_PREFIX).unwrap_or(&self.inner))
     }
 }
 
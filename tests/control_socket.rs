@@ -0,0 +1,81 @@
+//! Replacing directives through `LogBuilder::control_socket`
+#![cfg(unix)]
+
+extern crate slog;
+extern crate slog_envlogger;
+
+use std::env;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process;
+
+use slog::Level;
+use slog_envlogger::LogBuilder;
+
+fn send(path: &Path, spec: &str) -> String {
+    let mut stream = UnixStream::connect(path).unwrap();
+    stream.write_all(spec.as_bytes()).unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    reply
+}
+
+#[test]
+fn control_socket() {
+    let path = env::temp_dir().join(format!("envlogger-control-{}.sock", process::id()));
+    let logger = LogBuilder::new(slog::Discard).parse("info").control_socket(&path).build();
+    assert!(!logger.would_log(Level::Debug, "app"));
+
+    assert_eq!(send(&path, "info,app=debug\n"), "ok\n");
+    assert!(logger.would_log(Level::Debug, "app"));
+    assert!(!logger.would_log(Level::Debug, "net"));
+
+    // Nothing is applied from a spec with errors
+    let reply = send(&path, "warn,app=verbose");
+    assert!(reply.starts_with("error: "), "{}", reply);
+    assert!(logger.would_log(Level::Debug, "app"));
+    assert!(logger.would_log(Level::Info, "net"));
+
+    assert!(send(&path, " ").starts_with("error: "));
+
+    // Parts that can't be changed on a built logger aren't ignored
+    for spec in &["info/foo", "info;sample=10", "info,@user=alice=debug"] {
+        let reply = send(&path, spec);
+        assert!(reply.starts_with("error: "), "{}: {}", spec, reply);
+    }
+    assert!(logger.would_log(Level::Debug, "app"));
+
+    // A line is read, without waiting for the client to shut down its side
+    let stream = UnixStream::connect(&path).unwrap();
+    (&stream).write_all(b"info\n").unwrap();
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply).unwrap();
+    assert_eq!(reply, "ok\n");
+    assert!(!logger.would_log(Level::Debug, "app"));
+
+    drop(logger);
+    assert!(!path.exists());
+}
+
+#[test]
+fn control_socket_unavailable() {
+    let path = env::temp_dir().join(format!("envlogger-missing-{}", process::id())).join("control.sock");
+    let (builder, warnings) = LogBuilder::new(slog::Discard).collect_warnings();
+    let logger = builder.parse("info").control_socket(&path).build();
+    assert!(logger.would_log(Level::Info, "app"));
+    let warnings = warnings.lock().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("can't listen for directives"), "{}", warnings[0]);
+}
+
+#[test]
+fn control_socket_with_watch() {
+    let path = env::temp_dir().join(format!("envlogger-control-watch-{}.sock", process::id()));
+    let (builder, warnings) = LogBuilder::new(slog::Discard).collect_warnings();
+    let (_logger, _sender) = builder.parse("info").control_socket(&path).build_with_watch();
+    assert!(!path.exists());
+    assert_eq!(warnings.lock().unwrap().len(), 1);
+}
@@ -0,0 +1,46 @@
+//! `EnvLogger` must forward the wrapped drain's `Ok` and `Err` unchanged
+
+extern crate slog;
+extern crate slog_envlogger;
+
+use slog::{Drain, Level, OwnedKVList, Record, RecordStatic};
+use slog_envlogger::LogBuilder;
+
+#[derive(Debug, PartialEq)]
+enum StoreError {
+    Full(String),
+}
+
+/// Drain failing for every record, with the record's message
+struct FullStore;
+
+impl Drain for FullStore {
+    type Ok = Option<u32>;
+    type Err = StoreError;
+
+    fn log(&self, record: &Record, _: &OwnedKVList) -> Result<Option<u32>, StoreError> {
+        match record.level() {
+            Level::Error => Err(StoreError::Full(record.msg().to_string())),
+            _ => Ok(Some(7)),
+        }
+    }
+}
+
+#[test]
+fn custom_error() {
+    let logger = LogBuilder::new(FullStore).parse("info").build();
+    let kv = OwnedKVList::from(slog::o!());
+
+    static ERROR: RecordStatic<'static> = slog::record_static!(Level::Error, "");
+    let result = logger.log(&Record::new(&ERROR, &format_args!("disk"), slog::b!()), &kv);
+    assert_eq!(result, Err(StoreError::Full("disk".to_string())));
+
+    static INFO: RecordStatic<'static> = slog::record_static!(Level::Info, "");
+    let result = logger.log(&Record::new(&INFO, &format_args!("ok"), slog::b!()), &kv);
+    assert_eq!(result, Ok(Some(7)));
+
+    // Records that aren't passed on get the default `Ok`
+    static DEBUG: RecordStatic<'static> = slog::record_static!(Level::Debug, "");
+    let result = logger.log(&Record::new(&DEBUG, &format_args!("skipped"), slog::b!()), &kv);
+    assert_eq!(result, Ok(None));
+}
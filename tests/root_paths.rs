@@ -0,0 +1,15 @@
+//! `init` and `new` must stay available at the crate root
+
+extern crate slog;
+extern crate slog_envlogger;
+
+#[cfg(feature = "init")]
+use slog_envlogger::init;
+use slog_envlogger::{new, EnvLogger};
+
+#[test]
+fn root_paths() {
+    let _: EnvLogger<slog::Discard> = new(slog::Discard);
+    #[cfg(feature = "init")]
+    let _: fn() -> _ = init;
+}